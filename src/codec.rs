@@ -1,10 +1,15 @@
 use super::ids::*;
+use generic_array::typenum::Unsigned;
 use nom::{
-    error::ParseError, lib::std::ops::RangeFrom, number::streaming, IResult, InputIter,
-    InputLength, Slice,
+    bytes::streaming::take, error::ParseError, lib::std::ops::RangeFrom, number::streaming,
+    IResult, InputIter, InputLength, Slice,
 };
 
-const BASIC_CODEC_VERSION: u8 = 1;
+/// The codec version used by the stock Wio Terminal firmware.
+pub const BASIC_CODEC_VERSION: u8 = 1;
+
+/// The CRC seed used by the stock Wio Terminal firmware.
+pub const DEFAULT_CRC_SEED: u16 = 0xEF4A;
 
 /// Data precluding an RPC payload, describing which RPC it is and other metadata
 #[derive(Clone, Debug)]
@@ -18,7 +23,13 @@ pub struct Header {
 impl Header {
     /// Encodes the RPC into its wire format
     pub fn as_bytes(&self) -> [u8; 8] {
-        let header: u32 = (BASIC_CODEC_VERSION as u32) << 24
+        self.as_bytes_with_version(BASIC_CODEC_VERSION)
+    }
+
+    /// Encodes the RPC into its wire format, using the given codec version.
+    /// Only needed for firmware which does not use the basic codec version.
+    pub fn as_bytes_with_version(&self, version: u8) -> [u8; 8] {
+        let header: u32 = (version as u32) << 24
             | ((self.service as u32) << 16)
             | ((self.request as u32) << 8)
             | (self.msg_type as u32);
@@ -31,8 +42,33 @@ impl Header {
         ]
     }
 
-    /// Decodes an RPC header from a byte slice or other compatible type
+    /// Checks the header describes a reply to the given request, returning
+    /// Err::NotOurs otherwise. Headers with an unrecognized message type or
+    /// service never match.
+    pub fn expect_reply<E>(&self, service: Service, request: u8) -> Result<(), super::Err<E>> {
+        if self.msg_type != MsgType::Reply
+            || self.service == Service::Unknown
+            || self.service != service
+            || self.request != request
+        {
+            return Err(super::Err::NotOurs);
+        }
+        Ok(())
+    }
+
+    /// Decodes an RPC header from a byte slice or other compatible type. The
+    /// codec version is not checked; use parse_with_version to inspect it.
     pub fn parse<I, E: ParseError<I>>(i: I) -> IResult<I, Self, E>
+    where
+        I: Slice<RangeFrom<usize>> + InputIter<Item = u8> + InputLength,
+    {
+        let (i, (header, _)) = Self::parse_with_version(i)?;
+        Ok((i, header))
+    }
+
+    /// Decodes an RPC header like parse, also returning the codec version it
+    /// was encoded with.
+    pub fn parse_with_version<I, E: ParseError<I>>(i: I) -> IResult<I, (Self, u8), E>
     where
         I: Slice<RangeFrom<usize>> + InputIter<Item = u8> + InputLength,
     {
@@ -40,14 +76,51 @@ impl Header {
         let (i, sequence) = streaming::le_u32(i)?;
         Ok((
             i,
-            Self {
-                service: (((header >> 16) & 0xff) as u8).into(),
-                request: ((header >> 8) & 0xff) as u8,
-                msg_type: ((header & 0xff) as u8).into(),
-                sequence,
-            },
+            (
+                Self {
+                    service: (((header >> 16) & 0xff) as u8).into(),
+                    request: ((header >> 8) & 0xff) as u8,
+                    msg_type: ((header & 0xff) as u8).into(),
+                    sequence,
+                },
+                (header >> 24) as u8,
+            ),
         ))
     }
+
+    /// Decodes just the header of a frame body (the bytes following the
+    /// FrameHeader), such as to decide which RPC should parse it.
+    pub fn peek(frame_body: &[u8]) -> Result<Self, super::Err<()>> {
+        let (_, hdr) = Self::parse(frame_body)?;
+        Ok(hdr)
+    }
+}
+
+impl core::fmt::Display for Header {
+    /// Formats the header like "Reply Wifi/ScanGetAP seq=42".
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "{:?} {:?}/", self.msg_type, self.service)?;
+        match self.service {
+            Service::System => write_request(f, SystemRequest::ALL, self.request)?,
+            Service::Wifi => write_request(f, WifiRequest::ALL, self.request)?,
+            Service::TCPIP => write_request(f, TCPIPRequest::ALL, self.request)?,
+            Service::WifiCallback => write_request(f, WifiCallbackRequest::ALL, self.request)?,
+            _ => write!(f, "{}", self.request)?,
+        }
+        write!(f, " seq={}", self.sequence)
+    }
+}
+
+/// Writes the name of the request with the given ID, or the ID itself if
+/// it is not known.
+fn write_request<R>(f: &mut core::fmt::Formatter<'_>, all: &[R], request: u8) -> core::fmt::Result
+where
+    R: Copy + core::fmt::Debug + Into<u8>,
+{
+    match all.iter().find(|r| (**r).into() == request) {
+        Some(r) => write!(f, "{:?}", r),
+        None => write!(f, "{}", request),
+    }
 }
 
 /// Wraps a complete RPC (Header + data) on stream transports, like a UART.
@@ -60,9 +133,33 @@ pub struct FrameHeader {
 impl FrameHeader {
     /// Builds a frame header which will wrap the provided msg.
     pub fn new_from_msg(msg: &[u8]) -> Self {
+        Self::new_from_msg_with_seed(msg, DEFAULT_CRC_SEED)
+    }
+
+    /// Builds a frame header which will wrap the concatenation of the provided
+    /// parts (such as a header and arguments held in separate buffers),
+    /// without needing to copy them into a single buffer first.
+    pub fn new_from_parts(parts: &[&[u8]]) -> Self {
+        Self::new_from_parts_with_seed(parts, DEFAULT_CRC_SEED)
+    }
+
+    /// Builds a frame header which will wrap the concatenation of the provided
+    /// parts, computing the CRC from the given seed. Only needed for firmware
+    /// which does not use the default seed.
+    pub fn new_from_parts_with_seed(parts: &[&[u8]], seed: u16) -> Self {
+        Self {
+            msg_length: parts.iter().map(|p| p.len()).sum::<usize>() as u16,
+            crc16: crc16_chained_with_seed(parts, seed),
+        }
+    }
+
+    /// Builds a frame header which will wrap the provided msg, computing the
+    /// CRC from the given seed. Only needed for firmware which does not use
+    /// the default seed.
+    pub fn new_from_msg_with_seed(msg: &[u8], seed: u16) -> Self {
         Self {
             msg_length: msg.len() as u16,
-            crc16: crc16(msg),
+            crc16: crc16(msg, seed),
         }
     }
 
@@ -88,7 +185,15 @@ impl FrameHeader {
     where
         I: InputIter<Item = u8>,
     {
-        if crc16(data) == self.crc16 {
+        self.check_crc_with_seed(data, DEFAULT_CRC_SEED)
+    }
+
+    /// Checks the CRC matches that computed from the provided payload and seed.
+    pub fn check_crc_with_seed<I, E>(&self, data: I, seed: u16) -> Result<(), super::Err<E>>
+    where
+        I: InputIter<Item = u8>,
+    {
+        if crc16(data, seed) == self.crc16 {
             Ok(())
         } else {
             Err(super::Err::CRCMismatch)
@@ -96,14 +201,278 @@ impl FrameHeader {
     }
 }
 
+impl core::fmt::Display for FrameHeader {
+    /// Formats the frame header like "frame len=64 crc=0x1a2b".
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "frame len={} crc={:#06x}", self.msg_length, self.crc16)
+    }
+}
+
+/// Encodes a complete frame (frame header, header, and body) into out, such
+/// as a reply for use by a fake device. This is the inverse of decoding a
+/// frame with FrameHeader::parse and an RPC's parse().
+pub fn build_reply<N>(
+    header: &Header,
+    body: &[u8],
+    out: &mut heapless::Vec<u8, N>,
+) -> Result<(), super::Err<()>>
+where
+    N: heapless::ArrayLength<u8>,
+{
+    // Vec::clear trips a debug assertion in this version of heapless.
+    *out = heapless::Vec::new();
+    out.extend_from_slice(&[0u8; 4])
+        .and_then(|_| out.extend_from_slice(&header.as_bytes()))
+        .and_then(|_| out.extend_from_slice(body))
+        .map_err(|_| super::Err::ResponseOverrun)?;
+
+    let frame = FrameHeader::new_from_msg(&out[4..]).as_bytes();
+    out[..4].copy_from_slice(&frame);
+    Ok(())
+}
+
+/// The capacity of the buffer RPC arguments are encoded into, which must fit
+/// the largest arguments of any RPC.
+pub type ArgsCapacity = heapless::consts::U160;
+
+/// The maximum length of the encoded arguments of any RPC.
+pub const MAX_ARGS_LEN: usize = <ArgsCapacity as Unsigned>::USIZE;
+
+/// Writes RPC arguments into a buffer in their little-endian wire format.
+///
+/// Writes which do not fit in the buffer are dropped, along with any writes
+/// after them, and the overflow is reported by finish(). A truncated
+/// encoding is never silently produced.
+pub struct ArgWriter<'a> {
+    buff: &'a mut [u8],
+    len: usize,
+}
+
+impl<'a> ArgWriter<'a> {
+    /// Creates a writer which writes from the start of the provided buffer.
+    pub fn new(buff: &'a mut [u8]) -> Self {
+        Self { buff, len: 0 }
+    }
+
+    /// Returns the length of the arguments written so far, including any
+    /// bytes which did not fit in the buffer.
+    pub fn encoded_len(&self) -> usize {
+        self.len
+    }
+
+    /// Returns the number of bytes written, or Err::RequestOverrun if the
+    /// arguments did not fit in the buffer.
+    pub fn finish<E>(&self) -> Result<usize, super::Err<E>> {
+        if self.len > self.buff.len() {
+            Err(super::Err::RequestOverrun)
+        } else {
+            Ok(self.len)
+        }
+    }
+
+    pub fn put_u8(&mut self, v: u8) -> &mut Self {
+        self.put_bytes(&[v])
+    }
+
+    pub fn put_u16_le(&mut self, v: u16) -> &mut Self {
+        self.put_bytes(&v.to_le_bytes())
+    }
+
+    pub fn put_u32_le(&mut self, v: u32) -> &mut Self {
+        self.put_bytes(&v.to_le_bytes())
+    }
+
+    pub fn put_i32_le(&mut self, v: i32) -> &mut Self {
+        self.put_bytes(&v.to_le_bytes())
+    }
+
+    /// Writes the bytes as-is, with no length prefix.
+    pub fn put_bytes(&mut self, v: &[u8]) -> &mut Self {
+        // Once a write has failed, len is past the end of the buffer so all
+        // subsequent writes fail too.
+        if let Some(out) = self.buff.get_mut(self.len..self.len + v.len()) {
+            out.copy_from_slice(v);
+        }
+        self.len += v.len();
+        self
+    }
+
+    /// Writes the bytes preceeded by their length as a u32, which is how eRPC
+    /// encodes strings and binary fields.
+    pub fn put_len_prefixed(&mut self, v: &[u8]) -> &mut Self {
+        self.put_u32_le(v.len() as u32).put_bytes(v)
+    }
+
+    /// Writes an optional string using the eRPC nullable framing: a flag byte
+    /// (0 = present, 1 = null), followed by the length-prefixed string if
+    /// present.
+    pub fn put_nullable_str(&mut self, s: Option<&str>) -> &mut Self {
+        match s {
+            Some(s) => self.put_u8(0).put_len_prefixed(s.as_bytes()),
+            None => self.put_u8(1),
+        }
+    }
+}
+
+/// Reads little-endian values out of an RPC reply, failing with an error
+/// rather than panicking if the reply is too short.
+pub struct ArgReader<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> ArgReader<'a> {
+    /// Creates a reader over the provided reply data.
+    pub fn new(data: &'a [u8]) -> Self {
+        Self { data }
+    }
+
+    /// Returns the data which has not yet been read.
+    pub fn remaining(&self) -> &'a [u8] {
+        self.data
+    }
+
+    pub fn get_u8<E>(&mut self) -> Result<u8, super::Err<E>> {
+        let (data, v) = streaming::le_u8(self.data)?;
+        self.data = data;
+        Ok(v)
+    }
+
+    pub fn get_u16_le<E>(&mut self) -> Result<u16, super::Err<E>> {
+        let (data, v) = streaming::le_u16(self.data)?;
+        self.data = data;
+        Ok(v)
+    }
+
+    pub fn get_i16_le<E>(&mut self) -> Result<i16, super::Err<E>> {
+        let (data, v) = streaming::le_i16(self.data)?;
+        self.data = data;
+        Ok(v)
+    }
+
+    pub fn get_u32_le<E>(&mut self) -> Result<u32, super::Err<E>> {
+        let (data, v) = streaming::le_u32(self.data)?;
+        self.data = data;
+        Ok(v)
+    }
+
+    pub fn get_i32_le<E>(&mut self) -> Result<i32, super::Err<E>> {
+        let (data, v) = streaming::le_i32(self.data)?;
+        self.data = data;
+        Ok(v)
+    }
+
+    /// Reads the next n bytes.
+    pub fn get_bytes<E>(&mut self, n: usize) -> Result<&'a [u8], super::Err<E>> {
+        let (data, v) = take(n)(self.data)?;
+        self.data = data;
+        Ok(v)
+    }
+
+    /// Reads the next N bytes into an array.
+    pub fn get_array<E, const N: usize>(&mut self) -> Result<[u8; N], super::Err<E>> {
+        let mut out = [0u8; N];
+        out.copy_from_slice(self.get_bytes(N)?);
+        Ok(out)
+    }
+
+    /// Reads a u32 length prefix, checking it matches the number of bytes
+    /// which follow it, excluding a trailer of the given size (such as a
+    /// trailing status code). If the field is all there but the trailer is
+    /// cut short, this fails with Err::Incomplete rather than blaming the
+    /// field.
+    pub fn get_len_prefix<E>(&mut self, trailer: usize) -> Result<usize, super::Err<E>> {
+        let declared = self.get_u32_le()? as usize;
+        let len = self.data.len();
+        if len >= declared && len < declared + trailer {
+            return Err(super::Err::Incomplete {
+                needed: declared + trailer - len,
+            });
+        }
+        let actual = len.saturating_sub(trailer);
+        if declared != actual {
+            return Err(super::Err::LengthMismatch { declared, actual });
+        }
+        Ok(declared)
+    }
+
+    /// Reads bytes preceeded by their length as a u32.
+    pub fn get_len_prefixed<E>(&mut self) -> Result<&'a [u8], super::Err<E>> {
+        let length = self.get_u32_le()?;
+        self.get_bytes(length as usize)
+    }
+
+    /// Reads a UTF-8 string preceeded by its length as a u32.
+    pub fn get_len_prefixed_str<E>(&mut self) -> Result<&'a str, super::Err<E>> {
+        let s = self.get_len_prefixed()?;
+        core::str::from_utf8(s).map_err(|_| super::Err::Parsing(nom::Err::Error(())))
+    }
+
+    /// Reads a string in the eRPC nullable framing, as written by
+    /// ArgWriter::put_nullable_str.
+    pub fn get_nullable_str<E>(&mut self) -> Result<Option<&'a str>, super::Err<E>> {
+        if self.get_u8()? != 0 {
+            return Ok(None);
+        }
+        self.get_len_prefixed_str().map(Some)
+    }
+
+    /// Reads a string preceeded by its length as a u32 into a String, as
+    /// returned by the get-string RPCs. The length must match the bytes
+    /// which follow it, excluding a trailer of the given size. An empty
+    /// string is valid, while one too long for String<N> fails with
+    /// Err::ResponseOverrun.
+    pub fn get_len_prefixed_string<N, E>(
+        &mut self,
+        trailer: usize,
+    ) -> Result<heapless::String<N>, super::Err<E>>
+    where
+        N: heapless::ArrayLength<u8>,
+    {
+        let length = self.get_len_prefix(trailer)?;
+        let mut out = heapless::String::new();
+        if length > out.capacity() {
+            return Err(super::Err::ResponseOverrun);
+        }
+
+        for b in self.get_bytes(length)? {
+            out.push(*b as char)
+                .map_err(|_| super::Err::ResponseOverrun)?;
+        }
+        Ok(out)
+    }
+}
+
 /// computes the CRC value used in the Wio Terminal eRPC codec
-pub(crate) fn crc16<I>(data: I) -> u16
+pub(crate) fn crc16<I>(data: I, seed: u16) -> u16
 where
     I: InputIter<Item = u8>,
 {
-    let mut crc: u32 = 0xEF4A;
+    crc16_update(seed, data.iter_elements())
+}
+
+/// Computes the CRC value used in the Wio Terminal eRPC codec over several
+/// slices, giving the same result as computing it over their concatenation.
+pub fn crc16_chained(parts: &[&[u8]]) -> u16 {
+    crc16_chained_with_seed(parts, DEFAULT_CRC_SEED)
+}
+
+/// Computes the CRC over several slices like crc16_chained, starting from the
+/// given seed.
+pub fn crc16_chained_with_seed(parts: &[&[u8]], seed: u16) -> u16 {
+    parts
+        .iter()
+        .fold(seed, |crc, part| crc16_update(crc, part.iter().copied()))
+}
+
+/// Feeds data into a running CRC. Bits above the low 16 never affect the
+/// result, so the CRC can be carried between calls as a u16.
+fn crc16_update<I>(crc: u16, data: I) -> u16
+where
+    I: Iterator<Item = u8>,
+{
+    let mut crc: u32 = crc as u32;
 
-    for b in data.iter_elements() {
+    for b in data {
         crc ^= (b as u32) << 8;
         for _ in 0..8 {
             let mut temp: u32 = crc << 1;
@@ -116,3 +485,142 @@ where
 
     crc as u16
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Err;
+
+    #[test]
+    fn arg_writer_little_endian() {
+        let mut buff = [0u8; 11];
+        let mut w = ArgWriter::new(&mut buff);
+        w.put_u8(1)
+            .put_u16_le(0x0302)
+            .put_u32_le(0x07060504)
+            .put_i32_le(-2);
+        assert_eq!(w.finish::<()>(), Ok(11));
+        assert_eq!(buff, [1, 2, 3, 4, 5, 6, 7, 0xfe, 0xff, 0xff, 0xff]);
+    }
+
+    #[test]
+    fn arg_writer_overflow() {
+        let mut buff = [0u8; 10];
+        let mut w = ArgWriter::new(&mut buff);
+        w.put_bytes(&[1; 8]).put_u32_le(2).put_u8(3);
+        assert_eq!(w.encoded_len(), 13);
+        assert_eq!(w.finish::<()>(), Err(Err::RequestOverrun));
+        // Writes after the one which did not fit must not be written either.
+        assert_eq!(buff, [1, 1, 1, 1, 1, 1, 1, 1, 0, 0]);
+    }
+
+    #[test]
+    fn nullable_str_round_trip() {
+        let mut buff = [0u8; 16];
+        let mut w = ArgWriter::new(&mut buff);
+        w.put_nullable_str(Some("abc")).put_nullable_str(None);
+        assert_eq!(w.finish::<()>(), Ok(9));
+        assert_eq!(&buff[..9], &[0, 3, 0, 0, 0, b'a', b'b', b'c', 1]);
+
+        let mut r = ArgReader::new(&buff[..9]);
+        assert_eq!(r.get_nullable_str::<()>(), Ok(Some("abc")));
+        assert_eq!(r.get_nullable_str::<()>(), Ok(None));
+        assert!(r.remaining().is_empty());
+    }
+
+    #[test]
+    fn build_reply_frame() {
+        let header = Header {
+            sequence: 42,
+            msg_type: MsgType::Reply,
+            service: Service::Wifi,
+            request: WifiRequest::ScanGetNumAPs as u8,
+        };
+        let mut out = heapless::Vec::<u8, heapless::consts::U32>::new();
+        out.extend_from_slice(&[0xaa; 20]).unwrap();
+        build_reply(&header, &[3, 0], &mut out).unwrap();
+        assert_eq!(out.len(), 4 + 8 + 2);
+
+        let (payload, frame) = FrameHeader::parse::<_, ()>(&out[..]).unwrap();
+        assert_eq!(frame.msg_length, 10);
+        assert_eq!(frame.check_crc::<_, ()>(payload), Ok(()));
+        assert_eq!(&payload[..8], &header.as_bytes());
+        assert_eq!(&payload[8..], &[3, 0]);
+    }
+
+    #[test]
+    fn build_reply_overrun() {
+        let header = Header {
+            sequence: 0,
+            msg_type: MsgType::Reply,
+            service: Service::System,
+            request: SystemRequest::VersionID as u8,
+        };
+        let mut out = heapless::Vec::<u8, heapless::consts::U16>::new();
+        assert_eq!(
+            build_reply(&header, &[0; 5], &mut out),
+            Err(Err::ResponseOverrun)
+        );
+    }
+
+    #[test]
+    fn crc16_chained_matches_concatenated() {
+        let msg = [1u8, 2, 3, 4, 5, 6, 7, 8, 9, 10];
+        let parts: [&[u8]; 4] = [&msg[..3], &[], &msg[3..8], &msg[8..]];
+        assert_eq!(crc16_chained(&parts), crc16(&msg[..], DEFAULT_CRC_SEED));
+        assert_eq!(
+            crc16_chained_with_seed(&parts, 0x1234),
+            crc16(&msg[..], 0x1234)
+        );
+        assert_eq!(crc16_chained(&[]), DEFAULT_CRC_SEED);
+
+        let frame = FrameHeader::new_from_parts_with_seed(&parts, 0x1234);
+        assert_eq!(frame.msg_length, 10);
+        assert_eq!(frame.check_crc_with_seed::<_, ()>(&msg[..], 0x1234), Ok(()));
+        assert_eq!(
+            FrameHeader::new_from_parts(&parts).crc16,
+            FrameHeader::new_from_msg(&msg).crc16
+        );
+    }
+
+    #[test]
+    fn header_with_version() {
+        let header = Header {
+            sequence: 9,
+            msg_type: MsgType::Invocation,
+            service: Service::Wifi,
+            request: WifiRequest::ScanStart as u8,
+        };
+        let bytes = header.as_bytes_with_version(2);
+        assert_eq!(bytes[3], 2);
+        assert_eq!(&bytes[..3], &header.as_bytes()[..3]);
+
+        let (_, (decoded, version)) = Header::parse_with_version::<_, ()>(&bytes[..]).unwrap();
+        assert_eq!(version, 2);
+        assert_eq!(decoded.service, Service::Wifi);
+        assert_eq!(decoded.request, WifiRequest::ScanStart as u8);
+        assert_eq!(decoded.sequence, 9);
+    }
+
+    #[test]
+    fn header_peek() {
+        let header = Header {
+            sequence: 42,
+            msg_type: MsgType::Reply,
+            service: Service::TCPIP,
+            request: TCPIPRequest::GetIPInfo as u8,
+        };
+        let mut out = heapless::Vec::<u8, heapless::consts::U32>::new();
+        build_reply(&header, &[1, 2, 3, 4], &mut out).unwrap();
+
+        let peeked = Header::peek(&out[4..]).unwrap();
+        assert_eq!(peeked.msg_type, MsgType::Reply);
+        assert_eq!(peeked.service, Service::TCPIP);
+        assert_eq!(peeked.request, TCPIPRequest::GetIPInfo as u8);
+        assert_eq!(peeked.sequence, 42);
+        assert_eq!(
+            Header::peek(&out[4..10]).unwrap_err(),
+            Err::Incomplete { needed: 2 }
+        );
+    }
+}
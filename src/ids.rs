@@ -9,21 +9,30 @@ pub enum MsgType {
     Unknown = 255,
 }
 
-impl From<u8> for MsgType {
-    fn from(mt: u8) -> MsgType {
+impl MsgType {
+    /// Decodes a message type, returning the value back as an error if it is
+    /// not recognized rather than mapping it to Unknown. A TryFrom impl is
+    /// not possible as it would conflict with From<u8>.
+    pub fn try_from_u8(mt: u8) -> Result<MsgType, u8> {
         match mt {
-            0 => MsgType::Invocation,
-            1 => MsgType::Oneway,
-            2 => MsgType::Reply,
-            3 => MsgType::Notification,
-            _ => MsgType::Unknown,
+            0 => Ok(MsgType::Invocation),
+            1 => Ok(MsgType::Oneway),
+            2 => Ok(MsgType::Reply),
+            3 => Ok(MsgType::Notification),
+            _ => Err(mt),
         }
     }
 }
 
+impl From<u8> for MsgType {
+    fn from(mt: u8) -> MsgType {
+        MsgType::try_from_u8(mt).unwrap_or(MsgType::Unknown)
+    }
+}
+
 /// Wio Terminal services
 #[derive(Debug, Copy, Clone, PartialEq)]
-#[allow(unused)]
+#[allow(unused, clippy::upper_case_acronyms)]
 pub enum Service {
     System = 1,
     BLEHost = 2,
@@ -36,20 +45,36 @@ pub enum Service {
     Unknown = 255,
 }
 
-impl From<u8> for Service {
-    fn from(mt: u8) -> Service {
+impl Service {
+    /// Decodes a service ID, returning the value back as an error if it is
+    /// not recognized rather than mapping it to Unknown.
+    pub fn try_from_u8(mt: u8) -> Result<Service, u8> {
         match mt {
-            1 => Service::System,
-            2 => Service::BLEHost,
-            3 => Service::BLEGap,
-            4 => Service::BLEGapBone,
-            13 => Service::BLECallback,
-            14 => Service::Wifi,
-            15 => Service::TCPIP,
-            18 => Service::WifiCallback,
-            _ => Service::Unknown,
+            1 => Ok(Service::System),
+            2 => Ok(Service::BLEHost),
+            3 => Ok(Service::BLEGap),
+            4 => Ok(Service::BLEGapBone),
+            13 => Ok(Service::BLECallback),
+            14 => Ok(Service::Wifi),
+            15 => Ok(Service::TCPIP),
+            18 => Ok(Service::WifiCallback),
+            _ => Err(mt),
         }
     }
+
+    /// Returns true for the services the firmware uses to send unsolicited
+    /// notifications to the host. Frames for these services can arrive
+    /// before the reply to an outstanding RPC, and should be handled
+    /// separately rather than treated as a bad reply.
+    pub fn is_callback(&self) -> bool {
+        matches!(self, Service::BLECallback | Service::WifiCallback)
+    }
+}
+
+impl From<u8> for Service {
+    fn from(mt: u8) -> Service {
+        Service::try_from_u8(mt).unwrap_or(Service::Unknown)
+    }
 }
 
 /// Wio Terminal request IDs for the System service
@@ -60,6 +85,11 @@ pub enum SystemRequest {
     AckID = 2,
 }
 
+impl SystemRequest {
+    /// All known request IDs for the System service.
+    pub const ALL: &'static [Self] = &[SystemRequest::VersionID, SystemRequest::AckID];
+}
+
 impl From<SystemRequest> for u8 {
     fn from(r: SystemRequest) -> u8 {
         r as u8
@@ -76,6 +106,7 @@ pub enum WifiRequest {
     IsConnectedToAP = 4,
     IsUp = 5,
     GetMacAddress = 8,
+    GetRSSI = 19,
     TurnOn = 27,
     TurnOff = 28,
     ScanStart = 64,
@@ -84,6 +115,25 @@ pub enum WifiRequest {
     ScanGetNumAPs = 67,
 }
 
+impl WifiRequest {
+    /// All known request IDs for the Wifi service.
+    pub const ALL: &'static [Self] = &[
+        WifiRequest::Connect,
+        WifiRequest::ConnectBSSID,
+        WifiRequest::Disconnect,
+        WifiRequest::IsConnectedToAP,
+        WifiRequest::IsUp,
+        WifiRequest::GetMacAddress,
+        WifiRequest::GetRSSI,
+        WifiRequest::TurnOn,
+        WifiRequest::TurnOff,
+        WifiRequest::ScanStart,
+        WifiRequest::IsScanning,
+        WifiRequest::ScanGetAP,
+        WifiRequest::ScanGetNumAPs,
+    ];
+}
+
 impl From<WifiRequest> for u8 {
     fn from(r: WifiRequest) -> u8 {
         r as u8
@@ -114,8 +164,67 @@ pub enum TCPIPRequest {
     SetMAC = 18,
 }
 
+impl TCPIPRequest {
+    /// All known request IDs for the TCPIP service.
+    pub const ALL: &'static [Self] = &[
+        TCPIPRequest::AdapterInit,
+        TCPIPRequest::StaStart,
+        TCPIPRequest::APStart,
+        TCPIPRequest::Stop,
+        TCPIPRequest::Up,
+        TCPIPRequest::Down,
+        TCPIPRequest::GetIPInfo,
+        TCPIPRequest::SetIPInfo,
+        TCPIPRequest::SetDNSInfo,
+        TCPIPRequest::GetDNSInfo,
+        TCPIPRequest::DHCPServStart,
+        TCPIPRequest::DHCPServStop,
+        TCPIPRequest::DHCPClientStart,
+        TCPIPRequest::DHCPClientStop,
+        TCPIPRequest::SetHostname,
+        TCPIPRequest::GetHostname,
+        TCPIPRequest::GetMAC,
+        TCPIPRequest::SetMAC,
+    ];
+}
+
 impl From<TCPIPRequest> for u8 {
     fn from(r: TCPIPRequest) -> u8 {
         r as u8
     }
 }
+
+/// Wio Terminal request IDs for the WifiCallback service, which the firmware
+/// uses to notify the host. Connection state changes and scan completion are
+/// delivered as payloads of EventCallback rather than as separate requests.
+#[derive(Debug, Copy, Clone, PartialEq)]
+#[allow(unused)]
+pub enum WifiCallbackRequest {
+    EventCallback = 1,
+    DNSFound = 2,
+    Unknown = 255,
+}
+
+impl WifiCallbackRequest {
+    /// All known request IDs for the WifiCallback service.
+    pub const ALL: &'static [Self] = &[
+        WifiCallbackRequest::EventCallback,
+        WifiCallbackRequest::DNSFound,
+    ];
+}
+
+impl From<u8> for WifiCallbackRequest {
+    fn from(r: u8) -> WifiCallbackRequest {
+        match r {
+            1 => WifiCallbackRequest::EventCallback,
+            2 => WifiCallbackRequest::DNSFound,
+            _ => WifiCallbackRequest::Unknown,
+        }
+    }
+}
+
+impl From<WifiCallbackRequest> for u8 {
+    fn from(r: WifiCallbackRequest) -> u8 {
+        r as u8
+    }
+}
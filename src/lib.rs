@@ -1,4 +1,7 @@
 #![no_std]
+#[cfg(feature = "alloc")]
+extern crate alloc;
+
 use heapless::String;
 use no_std_net::Ipv4Addr;
 
@@ -7,12 +10,19 @@ extern crate bitflags;
 
 mod codec;
 mod ids;
+/// Helpers for working with the values returned by the RPCs.
+pub mod util;
 
 /// Encapsulates errors that might occur when issuing or processing eRPCs.
 #[derive(Debug, Clone, PartialEq)]
 pub enum Err<E> {
     /// Parsing via the nom crate indicated an error
     Parsing(nom::Err<()>),
+    /// The response was truncated: more bytes are needed to parse it. needed
+    /// is the number of additional bytes required, or 0 if unknown.
+    Incomplete {
+        needed: usize,
+    },
     /// The CRC was wrong
     CRCMismatch,
     /// There was an issue while transmitting
@@ -24,24 +34,106 @@ pub enum Err<E> {
     RPCErr(E),
     /// Too much data was present in the response
     ResponseOverrun,
+    /// The arguments of a request did not fit in the buffer they were being
+    /// encoded into.
+    RequestOverrun,
+    /// The reply header was valid but carried no payload, which the firmware
+    /// does when the subsystem is not ready yet. Retrying later may succeed.
+    EmptyReply,
+    /// The length prefix of a response did not match the number of bytes
+    /// which followed it.
+    LengthMismatch {
+        declared: usize,
+        actual: usize,
+    },
+    /// The length prefix of a response matched the bytes which followed it,
+    /// but was not the size the field must have.
+    UnexpectedLength {
+        declared: usize,
+        expected: usize,
+    },
     Unknown,
 }
 
 impl<E> From<nom::Err<()>> for Err<E> {
     fn from(nom_err: nom::Err<()>) -> Self {
-        Err::Parsing::<E>(nom_err)
+        match nom_err {
+            nom::Err::Incomplete(nom::Needed::Size(n)) => Err::Incomplete { needed: n.get() },
+            nom::Err::Incomplete(nom::Needed::Unknown) => Err::Incomplete { needed: 0 },
+            _ => Err::Parsing::<E>(nom_err),
+        }
     }
 }
 
-pub use codec::{FrameHeader, Header};
+pub use codec::{
+    build_reply, crc16_chained, crc16_chained_with_seed, ArgReader, ArgWriter, ArgsCapacity,
+    FrameHeader, Header, BASIC_CODEC_VERSION, DEFAULT_CRC_SEED, MAX_ARGS_LEN,
+};
+pub use ids::{MsgType, Service, SystemRequest, TCPIPRequest, WifiCallbackRequest, WifiRequest};
 
 /// Describes an RPC used by the system.
 pub trait RPC {
     type ReturnValue;
     type Error;
 
-    fn header(&self, seq: u32) -> Header;
-    fn args(&self, _buff: &mut heapless::Vec<u8, heapless::consts::U64>) {}
+    /// The service which handles the RPC.
+    const SERVICE: Service;
+    /// The ID of the RPC within its service.
+    const REQUEST: u8;
+
+    fn header(&self, seq: u32) -> Header {
+        Header {
+            sequence: seq,
+            msg_type: MsgType::Invocation,
+            service: Self::SERVICE,
+            request: Self::REQUEST,
+        }
+    }
+
+    /// Encodes the arguments of the RPC. RPCs which take arguments implement
+    /// this; callers encode the arguments with args() or args_into().
+    fn write_args(&self, _w: &mut ArgWriter) {}
+
+    /// Appends the encoded arguments to buff, failing with
+    /// Err::RequestOverrun if they do not fit.
+    fn args(&self, buff: &mut heapless::Vec<u8, ArgsCapacity>) -> Result<(), Err<Self::Error>> {
+        let mut scratch = [0u8; MAX_ARGS_LEN];
+        let len = self.args_into(&mut scratch)?;
+        buff.extend_from_slice(&scratch[..len])
+            .map_err(|_| Err::RequestOverrun)
+    }
+
+    /// Encodes the arguments into the provided buffer, returning the number
+    /// of bytes written, or Err::RequestOverrun if the buffer is too small.
+    fn args_into(&self, buf: &mut [u8]) -> Result<usize, Err<Self::Error>> {
+        let mut w = ArgWriter::new(buf);
+        self.write_args(&mut w);
+        w.finish()
+    }
+
+    /// Returns true if issuing the RPC more than once has the same effect as
+    /// issuing it once, so it is safe to retransmit if the reply is lost or
+    /// corrupt. Only pure queries should return true.
+    fn is_idempotent(&self) -> bool {
+        false
+    }
+
+    /// Returns how long to wait for the reply, if the RPC is known to take
+    /// longer (or shorter) than usual. None means the caller's default
+    /// timeout is appropriate.
+    fn timeout_hint(&self) -> Option<core::time::Duration> {
+        None
+    }
+
+    /// Returns the length of the encoded header and arguments, excluding
+    /// the frame header. This is the full length even if the arguments would
+    /// not fit in the args buffer, so over-large requests can be rejected
+    /// before encoding them.
+    fn encoded_len(&self) -> usize {
+        let mut w = ArgWriter::new(&mut []);
+        self.write_args(&mut w);
+        8 + w.encoded_len()
+    }
 
     fn parse(&mut self, data: &[u8]) -> Result<Self::ReturnValue, Err<Self::Error>>;
 }
@@ -65,6 +157,16 @@ pub enum L3Interface {
     AP = 1,
 }
 
+/// Specifies which of the DNS servers of an interface is affected by the
+/// command.
+#[derive(Debug, Clone, Copy)]
+#[repr(u32)]
+pub enum DNSSlot {
+    Main = 0,
+    Backup = 1,
+    Fallback = 2,
+}
+
 /// Possible modes of the Wifi PHY.
 #[derive(Debug, Copy, Clone)]
 #[repr(u32)]
@@ -77,6 +179,33 @@ pub enum WifiMode {
     P2P = 5,
 }
 
+impl WifiMode {
+    /// Returns the mode with the given name, as returned by name().
+    pub fn from_name(name: &str) -> Option<WifiMode> {
+        match name {
+            "none" => Some(WifiMode::None),
+            "station" => Some(WifiMode::Station),
+            "ap" => Some(WifiMode::AP),
+            "station+ap" => Some(WifiMode::StationAndAP),
+            "promiscuous" => Some(WifiMode::Promiscuous),
+            "p2p" => Some(WifiMode::P2P),
+            _ => None,
+        }
+    }
+
+    /// Returns a short name for the mode, suitable for configuration files.
+    pub fn name(&self) -> &'static str {
+        match self {
+            WifiMode::None => "none",
+            WifiMode::Station => "station",
+            WifiMode::AP => "ap",
+            WifiMode::StationAndAP => "station+ap",
+            WifiMode::Promiscuous => "promiscuous",
+            WifiMode::P2P => "p2p",
+        }
+    }
+}
+
 /// Describes the high-level type of a network.
 #[derive(Debug, Copy, Clone)]
 #[allow(dead_code)]
@@ -85,17 +214,17 @@ pub enum BssType {
     Infra = 0,
     Adhoc = 1,
     Any = 2,
-    Unknown = core::u32::MAX,
+    Unknown = u32::MAX,
 }
 
 impl From<u32> for BssType {
     fn from(orig: u32) -> Self {
         match orig {
-            0 => return BssType::Infra,
-            1 => return BssType::Adhoc,
-            2 => return BssType::Any,
-            _ => return BssType::Unknown,
-        };
+            0 => BssType::Infra,
+            1 => BssType::Adhoc,
+            2 => BssType::Any,
+            _ => BssType::Unknown,
+        }
     }
 }
 
@@ -126,6 +255,94 @@ bitflags! {
     }
 }
 
+impl Security {
+    /// Returns the raw value used on the wire, including any unknown bits.
+    pub fn as_wire(&self) -> u32 {
+        self.bits()
+    }
+
+    /// Reconstructs security flags from their raw wire value. Unlike
+    /// from_bits_truncate, unknown bits are retained, so the value returned
+    /// by as_wire round-trips exactly.
+    pub fn from_wire(bits: u32) -> Self {
+        // Security doesn't rely on its bits being a subset of the defined
+        // flags; unknown_bits exists to inspect them.
+        unsafe { Security::from_bits_unchecked(bits) }
+    }
+
+    /// Returns true if no WEP or WPA-family security and no cipher is in use.
+    /// Networks with security bits this crate does not know about are never
+    /// considered open.
+    pub fn is_open(&self) -> bool {
+        !self.intersects(
+            Security::WEP_ENABLED
+                | Security::WPA_SECURITY
+                | Security::WPA2_SECURITY
+                | Security::WPA3_SECURITY,
+        ) && !self.has_cipher()
+            && self.unknown_bits() == 0
+    }
+
+    /// Returns true if any of the TKIP, AES or AES-CMAC ciphers are enabled.
+    fn has_cipher(&self) -> bool {
+        self.intersects(Security::TKIP_ENABLED | Security::AES_ENABLED | Security::AES_CMAC_ENABLED)
+    }
+
+    /// Returns any set bits which do not correspond to a known flag, such as
+    /// those used by newer ciphers.
+    pub fn unknown_bits(&self) -> u32 {
+        self.bits() & !Security::all().bits()
+    }
+
+    /// Returns a short human-readable name for the security in use, like
+    /// "WPA2" or "Open".
+    pub fn describe(&self) -> &'static str {
+        let (wpa, wpa2, wpa3) = (
+            self.contains(Security::WPA_SECURITY),
+            self.contains(Security::WPA2_SECURITY),
+            self.contains(Security::WPA3_SECURITY),
+        );
+        match (wpa, wpa2, wpa3) {
+            (_, true, true) => "WPA2/WPA3",
+            (_, false, true) => "WPA3",
+            (true, true, false) => "WPA/WPA2",
+            (false, true, false) => "WPA2",
+            (true, false, false) => "WPA",
+            _ if self.contains(Security::WEP_ENABLED) => "WEP",
+            _ if self.unknown_bits() != 0 => "Unknown",
+            _ if self.has_cipher() => "Secured",
+            _ => "Open",
+        }
+    }
+}
+
+/// Named security configurations for joining a network, which map to the
+/// correct combination of Security flags.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum SecurityPreset {
+    Open,
+    Wep,
+    WpaPsk,
+    Wpa2Psk,
+    Wpa2Wpa3Mixed,
+    Wpa3Sae,
+}
+
+impl From<SecurityPreset> for Security {
+    fn from(preset: SecurityPreset) -> Self {
+        match preset {
+            SecurityPreset::Open => Security::empty(),
+            SecurityPreset::Wep => Security::WEP_PSK,
+            SecurityPreset::WpaPsk => Security::WPA_TKIP_PSK,
+            SecurityPreset::Wpa2Psk => Security::WPA2_AES_PSK,
+            SecurityPreset::Wpa2Wpa3Mixed => {
+                Security::WPA2_SECURITY | Security::WPA3_SECURITY | Security::AES_ENABLED
+            }
+            SecurityPreset::Wpa3Sae => Security::WPS3_AES_PSK,
+        }
+    }
+}
+
 /// Valid WPS modes.
 #[derive(Debug, Copy, Clone)]
 #[allow(dead_code)]
@@ -145,16 +362,16 @@ pub enum WPS {
 impl From<u32> for WPS {
     fn from(orig: u32) -> Self {
         match orig {
-            0 => return WPS::Default,
-            1 => return WPS::UserSpecifed,
-            2 => return WPS::MachineSpecified,
-            3 => return WPS::Rekey,
-            4 => return WPS::Pushbutton,
-            5 => return WPS::RegistrarSpecified,
-            6 => return WPS::None,
-            7 => return WPS::Wsc,
-            _ => return WPS::Unknown,
-        };
+            0 => WPS::Default,
+            1 => WPS::UserSpecifed,
+            2 => WPS::MachineSpecified,
+            3 => WPS::Rekey,
+            4 => WPS::Pushbutton,
+            5 => WPS::RegistrarSpecified,
+            6 => WPS::None,
+            7 => WPS::Wsc,
+            _ => WPS::Unknown,
+        }
     }
 }
 
@@ -171,69 +388,161 @@ pub enum Band {
 impl From<u32> for Band {
     fn from(orig: u32) -> Self {
         match orig {
-            0 => return Band::_5Ghz,
-            1 => return Band::_24Ghz,
-            _ => return Band::Unknown,
-        };
+            0 => Band::_5Ghz,
+            1 => Band::_24Ghz,
+            _ => Band::Unknown,
+        }
     }
 }
 
 /// The machine-readable network name (6-bytes).
 #[derive(Copy, Clone)]
-#[repr(packed)]
+#[repr(C, packed)]
 pub struct BSSID(pub [u8; 6]);
 
-impl core::fmt::Debug for BSSID {
+impl BSSID {
+    /// Returns the address if it is usable as a station MAC, or None if the
+    /// multicast bit is set (which includes the broadcast address).
+    pub fn unicast(bytes: [u8; 6]) -> Option<Self> {
+        let addr = Self(bytes);
+        if addr.is_multicast() {
+            None
+        } else {
+            Some(addr)
+        }
+    }
+
+    /// Returns true if the multicast bit (LSB of the first octet) is set.
+    pub fn is_multicast(&self) -> bool {
+        self.0[0] & 0x01 != 0
+    }
+
+    /// Returns true if this is the broadcast address ff:ff:ff:ff:ff:ff.
+    pub fn is_broadcast(&self) -> bool {
+        self.0 == [0xff; 6]
+    }
+
+    /// Returns true if the locally-administered bit is set, as it should be
+    /// for a MAC chosen by the application rather than the vendor.
+    pub fn is_locally_administered(&self) -> bool {
+        self.0[0] & 0x02 != 0
+    }
+}
+
+impl core::fmt::Display for BSSID {
+    /// Formats the BSSID as colon-separated lowercase hex, like
+    /// "01:23:45:67:89:ab".
     fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
-        let table = b"0123456789abcdef";
-
-        let mut out = [0u8; 12 + 6 - 1];
-        for i in 0..(12 + 6 - 1) {
-            let b = self.0[i / 3];
-            out[i] = match (i + 1) % 3 {
-                0 => ':' as u8,
-                1 => table[(b >> 4) as usize],
-                2 => table[(b & 0xf) as usize],
-                _ => '?' as u8,
+        let bytes = self.0;
+        for (i, b) in bytes.iter().enumerate() {
+            if i > 0 {
+                f.write_str(":")?;
             }
+            write!(f, "{:02x}", b)?;
         }
+        Ok(())
+    }
+}
+
+impl core::str::FromStr for BSSID {
+    type Err = ();
 
-        f.write_str(core::str::from_utf8(&out).unwrap())
+    /// Parses colon-separated hex, like "01:23:45:67:89:ab", as returned by
+    /// GetMacAddress.
+    fn from_str(s: &str) -> Result<Self, ()> {
+        let mut out = [0u8; 6];
+        let mut parts = s.split(':');
+        for b in out.iter_mut() {
+            let part = parts.next().ok_or(())?;
+            if part.len() != 2 || !part.bytes().all(|c| c.is_ascii_hexdigit()) {
+                return Err(());
+            }
+            *b = u8::from_str_radix(part, 16).map_err(|_| ())?;
+        }
+        if parts.next().is_some() {
+            return Err(());
+        }
+        Ok(BSSID(out))
+    }
+}
+
+impl core::fmt::Debug for BSSID {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        core::fmt::Display::fmt(self, f)
     }
 }
 
 /// A human-readable network name.
 #[derive(Copy, Clone)]
-#[repr(packed)]
+#[repr(C, packed)]
 pub struct SSID {
     len: u8,
     value: [u8; 33],
 }
 
-impl core::fmt::Debug for SSID {
-    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+impl SSID {
+    /// Builds an SSID from raw bytes, which need not be valid UTF-8 and may
+    /// contain NULs. Returns None if there are more than 32 bytes.
+    pub fn from_bytes(bytes: &[u8]) -> Option<Self> {
+        if bytes.len() > 32 {
+            return None;
+        }
+        let mut value = [0u8; 33];
+        value[..bytes.len()].copy_from_slice(bytes);
+        Some(SSID {
+            len: bytes.len() as u8,
+            value,
+        })
+    }
+
+    /// Returns the raw bytes of the SSID.
+    pub fn as_bytes(&self) -> &[u8] {
         // Unused unsafe warning is erroneous: needed for safe_packed_borrows
         #[allow(unused_unsafe)]
         unsafe {
-            f.write_str(core::str::from_utf8(&self.value[..self.len as usize]).unwrap())
+            &self.value[..self.len as usize]
+        }
+    }
+}
+
+impl core::str::FromStr for SSID {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, ()> {
+        SSID::from_bytes(s.as_bytes()).ok_or(())
+    }
+}
+
+impl core::fmt::Debug for SSID {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match core::str::from_utf8(self.as_bytes()) {
+            Ok(s) => f.write_str(s),
+            // Hidden or misconfigured networks may use arbitrary bytes.
+            Err(_) => {
+                for b in self.as_bytes() {
+                    match b {
+                        0x20..=0x7e => write!(f, "{}", *b as char)?,
+                        _ => write!(f, "\\x{:02x}", b)?,
+                    }
+                }
+                Ok(())
+            }
         }
     }
 }
 
-impl<N> Into<String<N>> for SSID
+/// Converts the SSID into a string, failing if the SSID is not valid UTF-8 or
+/// is longer than the capacity of the string.
+impl<N> core::convert::TryFrom<SSID> for String<N>
 where
     N: heapless::ArrayLength<u8>,
 {
-    fn into(self) -> String<N> {
+    type Error = ();
+
+    fn try_from(ssid: SSID) -> Result<String<N>, ()> {
         let mut out = String::new();
-        // Unused unsafe warning is erroneous: needed for safe_packed_borrows
-        #[allow(unused_unsafe)]
-        unsafe {
-            for i in 0..self.len as usize {
-                out.push(self.value[i] as char).ok();
-            }
-        }
-        out
+        out.push_str(core::str::from_utf8(ssid.as_bytes()).map_err(|_| ())?)?;
+        Ok(out)
     }
 }
 
@@ -244,3 +553,223 @@ pub struct IPInfo {
     pub netmask: Ipv4Addr,
     pub gateway: Ipv4Addr,
 }
+
+/// Describes the DNS servers used by an interface, as assembled from
+/// GetDNSInfo queries of the main and backup slots.
+#[derive(Debug, Clone)]
+pub struct DNSInfo {
+    pub primary: Ipv4Addr,
+    pub secondary: Option<Ipv4Addr>,
+}
+
+impl DNSInfo {
+    /// Assembles the DNS servers from the results of GetDNSInfo for the main
+    /// and backup slots, treating 0.0.0.0 as unset. If only the backup slot
+    /// is set it becomes the primary. Returns None if neither slot is set.
+    pub fn from_slots(main: Option<Ipv4Addr>, backup: Option<Ipv4Addr>) -> Option<Self> {
+        let main = main.filter(|a| !a.is_unspecified());
+        let backup = backup.filter(|a| !a.is_unspecified());
+        match (main, backup) {
+            (Some(primary), secondary) => Some(DNSInfo { primary, secondary }),
+            (None, Some(primary)) => Some(DNSInfo {
+                primary,
+                secondary: None,
+            }),
+            (None, None) => None,
+        }
+    }
+}
+
+/// Describes the full network configuration of an interface. The firmware
+/// has no single RPC which reports this, so it must be assembled from the
+/// results of GetIPInfo and GetDNSInfo.
+#[derive(Debug, Clone)]
+pub struct NetworkConfig {
+    pub ip: IPInfo,
+    pub dns: Option<DNSInfo>,
+}
+
+impl NetworkConfig {
+    /// Assembles the configuration from the result of GetIPInfo and the
+    /// results of GetDNSInfo for the main and backup slots.
+    pub fn from_replies(ip: IPInfo, main: Option<Ipv4Addr>, backup: Option<Ipv4Addr>) -> Self {
+        NetworkConfig {
+            ip,
+            dns: DNSInfo::from_slots(main, backup),
+        }
+    }
+}
+
+/// A firmware version, as parsed from the string returned by GetVersion.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub struct FirmwareVersion {
+    pub major: u8,
+    pub minor: u8,
+    pub patch: u8,
+}
+
+impl core::str::FromStr for FirmwareVersion {
+    type Err = ();
+
+    /// Parses versions of the form "2.1.3" or "v2.1.3".
+    fn from_str(s: &str) -> Result<Self, ()> {
+        let s = s.trim_matches(|c: char| c == '\0' || c.is_whitespace());
+        let s = s.strip_prefix(|c| c == 'v' || c == 'V').unwrap_or(s);
+
+        let mut parts = s.split('.');
+        let mut next = || -> Result<u8, ()> { parts.next().ok_or(())?.parse().map_err(|_| ()) };
+        let (major, minor, patch) = (next()?, next()?, next()?);
+        if parts.next().is_some() {
+            return Err(());
+        }
+
+        Ok(FirmwareVersion {
+            major,
+            minor,
+            patch,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use core::convert::TryFrom;
+    use heapless::consts::U10;
+
+    #[test]
+    fn ssid_to_short_string() {
+        let ssid: SSID = "0123456789abcdefghij".parse().unwrap();
+        assert_eq!(String::<U10>::try_from(ssid), Err(()));
+        assert_eq!(
+            String::<heapless::consts::U32>::try_from(ssid)
+                .unwrap()
+                .as_str(),
+            "0123456789abcdefghij"
+        );
+    }
+
+    #[test]
+    fn ssid_to_string_utf8() {
+        let ssid: SSID = "café".parse().unwrap();
+        assert_eq!(String::<U10>::try_from(ssid).unwrap().as_str(), "café");
+
+        let ssid = SSID::from_bytes(&[b'a', 0xff]).unwrap();
+        assert_eq!(String::<U10>::try_from(ssid), Err(()));
+    }
+
+    #[test]
+    fn security_preset_bits() {
+        let bits = |p| Security::from(p).as_wire();
+        assert_eq!(bits(SecurityPreset::Open), 0);
+        assert_eq!(bits(SecurityPreset::Wep), 0x0000_0001);
+        assert_eq!(bits(SecurityPreset::WpaPsk), 0x0020_0002);
+        assert_eq!(bits(SecurityPreset::Wpa2Psk), 0x0040_0004);
+        assert_eq!(bits(SecurityPreset::Wpa2Wpa3Mixed), 0x00c0_0004);
+        assert_eq!(bits(SecurityPreset::Wpa3Sae), 0x0080_0004);
+    }
+
+    #[test]
+    fn bssid_format() {
+        use core::fmt::Write;
+
+        let bssid = BSSID([0x01, 0x23, 0x45, 0x67, 0x89, 0xab]);
+        let mut out: String<heapless::consts::U32> = String::new();
+        write!(out, "{}", bssid).unwrap();
+        assert_eq!(out.as_str(), "01:23:45:67:89:ab");
+        let mut out: String<heapless::consts::U32> = String::new();
+        write!(out, "{:?}", bssid).unwrap();
+        assert_eq!(out.as_str(), "01:23:45:67:89:ab");
+    }
+
+    #[test]
+    fn bssid_from_str() {
+        use core::fmt::Write;
+
+        let bssid: BSSID = "01:23:45:67:89:AB".parse().unwrap();
+        assert_eq!(bssid.0, [0x01, 0x23, 0x45, 0x67, 0x89, 0xab]);
+
+        let mut out: String<heapless::consts::U32> = String::new();
+        write!(out, "{}", bssid).unwrap();
+        assert_eq!(out.parse::<BSSID>().unwrap().0, bssid.0);
+
+        assert!("01:23:45:67:89".parse::<BSSID>().is_err());
+        assert!("01:23:45:67:89:ab:cd".parse::<BSSID>().is_err());
+        assert!("01:23:45:67:89:+b".parse::<BSSID>().is_err());
+    }
+
+    #[test]
+    fn ssid_from_bytes() {
+        use core::fmt::Write;
+
+        let ssid = SSID::from_bytes(b"ab\0cd").unwrap();
+        assert_eq!(ssid.as_bytes(), b"ab\0cd");
+        assert!(SSID::from_bytes(&[b'a'; 32]).is_some());
+        assert!(SSID::from_bytes(&[b'a'; 33]).is_none());
+
+        let ssid = SSID::from_bytes(&[b'a', 0xff, 0x00, b'b']).unwrap();
+        let mut out: String<heapless::consts::U32> = String::new();
+        write!(out, "{:?}", ssid).unwrap();
+        assert_eq!(out.as_str(), "a\\xff\\x00b");
+    }
+
+    #[test]
+    fn bssid_unicast() {
+        let normal = [0x02, 0x11, 0x22, 0x33, 0x44, 0x55];
+        assert_eq!(BSSID::unicast(normal).unwrap().0, normal);
+        assert!(BSSID::unicast([0x01, 0x00, 0x5e, 0x00, 0x00, 0x01]).is_none());
+        assert!(BSSID::unicast([0xff; 6]).is_none());
+        assert!(BSSID([0xff; 6]).is_broadcast());
+    }
+
+    #[test]
+    fn firmware_version_from_str() {
+        let want = FirmwareVersion {
+            major: 2,
+            minor: 1,
+            patch: 3,
+        };
+        assert_eq!("2.1.3".parse(), Ok(want));
+        assert_eq!("v2.1.3".parse(), Ok(want));
+        assert_eq!("2.1".parse::<FirmwareVersion>(), Err(()));
+        assert_eq!("2.1.3.4".parse::<FirmwareVersion>(), Err(()));
+        assert_eq!("2.x.3".parse::<FirmwareVersion>(), Err(()));
+    }
+
+    #[test]
+    fn security_is_open() {
+        assert!(Security::empty().is_open());
+        assert!(!Security::WEP_PSK.is_open());
+        assert!(!Security::WPA2_AES_PSK.is_open());
+        assert!(Security::WPS_OPEN.is_open());
+        assert!(!Security::WPS_SECURE.is_open());
+        assert_eq!(Security::WPS_SECURE.describe(), "Secured");
+        assert!(!Security::from_wire(0x2000_0000).is_open());
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod testing {
+    use super::*;
+
+    /// Frames body as R's reply with build_reply, checks the framing, and
+    /// parses the payload with rpc.
+    pub(crate) fn parse_reply<R: RPC>(
+        rpc: &mut R,
+        body: &[u8],
+    ) -> Result<R::ReturnValue, Err<R::Error>> {
+        let header = Header {
+            sequence: 1,
+            msg_type: MsgType::Reply,
+            service: R::SERVICE,
+            request: R::REQUEST,
+        };
+        let mut frame = heapless::Vec::<u8, heapless::consts::U512>::new();
+        build_reply(&header, body, &mut frame).unwrap();
+
+        let (payload, frame_header) = FrameHeader::parse::<_, ()>(&frame[..]).unwrap();
+        assert_eq!(frame_header.msg_length as usize, payload.len());
+        frame_header.check_crc::<_, ()>(payload).unwrap();
+        rpc.parse(payload)
+    }
+}
@@ -1,41 +1,85 @@
 use super::{codec, ids, Err};
 use heapless::{consts::U16, String};
-use nom::{number::streaming, InputIter};
 
-/// Returns a string indicating the firmware version on the wifi chip.
+/// Returns a string indicating the firmware version on the wifi chip, which
+/// can be parsed into a FirmwareVersion.
+///
+/// The firmware has no RPC to report which features it supports, so
+/// comparing the version against the release which introduced a feature is
+/// the only way to check for it before use.
 pub struct GetVersion {}
 
 impl super::RPC for GetVersion {
     type ReturnValue = String<U16>;
     type Error = ();
 
-    fn header(&self, seq: u32) -> codec::Header {
-        codec::Header {
-            sequence: seq,
-            msg_type: ids::MsgType::Invocation,
-            service: ids::Service::System,
-            request: ids::SystemRequest::VersionID.into(),
-        }
+    const SERVICE: ids::Service = ids::Service::System;
+    const REQUEST: u8 = ids::SystemRequest::VersionID as u8;
+
+    fn is_idempotent(&self) -> bool {
+        true
     }
 
     fn parse(&mut self, data: &[u8]) -> Result<String<U16>, Err<()>> {
         let (data, hdr) = codec::Header::parse(data)?;
-        if hdr.msg_type != ids::MsgType::Reply
-            || hdr.service != ids::Service::System
-            || hdr.request != ids::SystemRequest::VersionID.into()
-        {
-            return Err(Err::NotOurs);
+        hdr.expect_reply(Self::SERVICE, Self::REQUEST)?;
+        if data.is_empty() {
+            return Err(Err::EmptyReply);
         }
 
-        let (data, length) = streaming::le_u32(data)?;
-        if length > 16 {
-            return Err(Err::ResponseOverrun);
-        }
+        codec::ArgReader::new(data).get_len_prefixed_string(0)
+    }
+}
 
-        let mut out: Self::ReturnValue = String::new();
-        for b in data.iter_elements() {
-            out.push(b as char).map_err(|_| Err::ResponseOverrun)?;
-        }
-        Ok(out)
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::parse_reply;
+
+    #[test]
+    fn get_version() {
+        let version = parse_reply(&mut GetVersion {}, b"\x05\x00\x00\x002.1.3").unwrap();
+        assert_eq!(version.as_str(), "2.1.3");
+        assert_eq!(
+            version.parse(),
+            Ok(crate::FirmwareVersion {
+                major: 2,
+                minor: 1,
+                patch: 3
+            })
+        );
+    }
+
+    #[test]
+    fn get_version_wrong_length() {
+        assert_eq!(
+            parse_reply(&mut GetVersion {}, b"\x06\x00\x00\x002.1.3"),
+            Err(crate::Err::LengthMismatch {
+                declared: 6,
+                actual: 5
+            })
+        );
+    }
+
+    #[test]
+    fn get_version_empty_and_too_long() {
+        assert_eq!(
+            parse_reply(&mut GetVersion {}, &[0, 0, 0, 0])
+                .unwrap()
+                .as_str(),
+            ""
+        );
+        assert_eq!(
+            parse_reply(&mut GetVersion {}, b"\x11\x00\x00\x000123456789abcdefg"),
+            Err(crate::Err::ResponseOverrun)
+        );
+    }
+
+    #[test]
+    fn get_version_empty_reply() {
+        assert_eq!(
+            parse_reply(&mut GetVersion {}, &[]),
+            Err(crate::Err::EmptyReply)
+        );
     }
 }
@@ -1,7 +1,8 @@
 #[allow(dead_code)]
 use super::{codec, ids, Err};
+use heapless::{consts::U32, String};
 use no_std_net::Ipv4Addr;
-use nom::{bytes::streaming::take, number::streaming};
+use nom::number::streaming;
 
 /// Initializes the layer 3 subsystem.
 pub struct AdapterInit {}
@@ -10,28 +11,44 @@ impl super::RPC for AdapterInit {
     type ReturnValue = ();
     type Error = ();
 
-    fn header(&self, seq: u32) -> codec::Header {
-        codec::Header {
-            sequence: seq,
-            msg_type: ids::MsgType::Invocation,
-            service: ids::Service::TCPIP,
-            request: ids::TCPIPRequest::AdapterInit.into(),
-        }
-    }
+    const SERVICE: ids::Service = ids::Service::TCPIP;
+    const REQUEST: u8 = ids::TCPIPRequest::AdapterInit as u8;
 
     fn parse(&mut self, data: &[u8]) -> Result<Self::ReturnValue, Err<Self::Error>> {
         let (_, hdr) = codec::Header::parse(data)?;
-        if hdr.msg_type != ids::MsgType::Reply
-            || hdr.service != ids::Service::TCPIP
-            || hdr.request != ids::TCPIPRequest::AdapterInit.into()
-        {
-            return Err(Err::NotOurs);
-        }
+        hdr.expect_reply(Self::SERVICE, Self::REQUEST)?;
 
         Ok(())
     }
 }
 
+/// Tears down layer 3 on the given interface. The firmware has no
+/// counterpart to AdapterInit, so to reinitialize the network stack after an
+/// error, stop each interface in use and then issue AdapterInit again.
+pub struct AdapterStop {
+    pub interface: super::L3Interface,
+}
+
+impl super::RPC for AdapterStop {
+    type ReturnValue = i32;
+    type Error = ();
+
+    const SERVICE: ids::Service = ids::Service::TCPIP;
+    const REQUEST: u8 = ids::TCPIPRequest::Stop as u8;
+
+    fn write_args(&self, w: &mut codec::ArgWriter) {
+        w.put_u32_le(self.interface as u32);
+    }
+
+    fn parse(&mut self, data: &[u8]) -> Result<Self::ReturnValue, Err<Self::Error>> {
+        let (data, hdr) = codec::Header::parse(data)?;
+        hdr.expect_reply(Self::SERVICE, Self::REQUEST)?;
+
+        let (_, ret_val) = streaming::le_i32(data)?;
+        Ok(ret_val)
+    }
+}
+
 /// Stops any DHCP client management.
 pub struct DHCPClientStop {
     pub interface: super::L3Interface,
@@ -41,28 +58,16 @@ impl super::RPC for DHCPClientStop {
     type ReturnValue = i32;
     type Error = ();
 
-    fn args(&self, buff: &mut heapless::Vec<u8, heapless::consts::U64>) {
-        let interface_id = self.interface as u32;
-        buff.extend_from_slice(&interface_id.to_le_bytes()).ok();
-    }
+    const SERVICE: ids::Service = ids::Service::TCPIP;
+    const REQUEST: u8 = ids::TCPIPRequest::DHCPClientStop as u8;
 
-    fn header(&self, seq: u32) -> codec::Header {
-        codec::Header {
-            sequence: seq,
-            msg_type: ids::MsgType::Invocation,
-            service: ids::Service::TCPIP,
-            request: ids::TCPIPRequest::DHCPClientStop.into(),
-        }
+    fn write_args(&self, w: &mut codec::ArgWriter) {
+        w.put_u32_le(self.interface as u32);
     }
 
     fn parse(&mut self, data: &[u8]) -> Result<Self::ReturnValue, Err<Self::Error>> {
         let (data, hdr) = codec::Header::parse(data)?;
-        if hdr.msg_type != ids::MsgType::Reply
-            || hdr.service != ids::Service::TCPIP
-            || hdr.request != ids::TCPIPRequest::DHCPClientStop.into()
-        {
-            return Err(Err::NotOurs);
-        }
+        hdr.expect_reply(Self::SERVICE, Self::REQUEST)?;
 
         let (_, ret_val) = streaming::le_i32(data)?;
         Ok(ret_val)
@@ -78,84 +83,447 @@ impl super::RPC for DHCPClientStart {
     type ReturnValue = i32;
     type Error = ();
 
-    fn args(&self, buff: &mut heapless::Vec<u8, heapless::consts::U64>) {
-        let interface_id = self.interface as u32;
-        buff.extend_from_slice(&interface_id.to_le_bytes()).ok();
-    }
+    const SERVICE: ids::Service = ids::Service::TCPIP;
+    const REQUEST: u8 = ids::TCPIPRequest::DHCPClientStart as u8;
 
-    fn header(&self, seq: u32) -> codec::Header {
-        codec::Header {
-            sequence: seq,
-            msg_type: ids::MsgType::Invocation,
-            service: ids::Service::TCPIP,
-            request: ids::TCPIPRequest::DHCPClientStart.into(),
-        }
+    fn write_args(&self, w: &mut codec::ArgWriter) {
+        w.put_u32_le(self.interface as u32);
     }
 
     fn parse(&mut self, data: &[u8]) -> Result<Self::ReturnValue, Err<Self::Error>> {
         let (data, hdr) = codec::Header::parse(data)?;
-        if hdr.msg_type != ids::MsgType::Reply
-            || hdr.service != ids::Service::TCPIP
-            || hdr.request != ids::TCPIPRequest::DHCPClientStart.into()
-        {
-            return Err(Err::NotOurs);
-        }
+        hdr.expect_reply(Self::SERVICE, Self::REQUEST)?;
 
         let (_, ret_val) = streaming::le_i32(data)?;
         Ok(ret_val)
     }
 }
 
-/// Returns the IP configuration the station is using.
+/// Returns the IP configuration the station is using, or None if the
+/// interface has no address yet (such as while DHCP is still in progress).
 pub struct GetIPInfo {
     pub interface: super::L3Interface,
 }
 
 impl super::RPC for GetIPInfo {
-    type ReturnValue = super::IPInfo;
+    type ReturnValue = Option<super::IPInfo>;
     type Error = i32;
 
-    fn header(&self, seq: u32) -> codec::Header {
-        codec::Header {
-            sequence: seq,
-            msg_type: ids::MsgType::Invocation,
-            service: ids::Service::TCPIP,
-            request: ids::TCPIPRequest::GetIPInfo.into(),
-        }
+    const SERVICE: ids::Service = ids::Service::TCPIP;
+    const REQUEST: u8 = ids::TCPIPRequest::GetIPInfo as u8;
+
+    fn is_idempotent(&self) -> bool {
+        true
     }
 
-    fn args(&self, buff: &mut heapless::Vec<u8, heapless::consts::U64>) {
-        let interface_id = self.interface as u32;
-        buff.extend_from_slice(&interface_id.to_le_bytes()).ok();
+    fn write_args(&self, w: &mut codec::ArgWriter) {
+        w.put_u32_le(self.interface as u32);
     }
 
     fn parse(&mut self, data: &[u8]) -> Result<Self::ReturnValue, Err<Self::Error>> {
         let (data, hdr) = codec::Header::parse(data)?;
-        if hdr.msg_type != ids::MsgType::Reply
-            || hdr.service != ids::Service::TCPIP
-            || hdr.request != ids::TCPIPRequest::GetIPInfo.into()
-        {
-            return Err(Err::NotOurs);
+        hdr.expect_reply(Self::SERVICE, Self::REQUEST)?;
+        if data.is_empty() {
+            return Err(Err::EmptyReply);
         }
 
-        let (data, payload_length) = streaming::le_u32(data)?;
-        if payload_length != 12 {
-            return Err(Err::RPCErr(1));
+        let mut reply = codec::ArgReader::new(data);
+        let length = reply.get_len_prefix(4)?;
+        if length != 12 {
+            return Err(Err::UnexpectedLength {
+                declared: length,
+                expected: 12,
+            });
         }
 
-        let (data, ip) = take(4u8)(data)?;
-        let (data, mask) = take(4u8)(data)?;
-        let (data, gateway) = take(4u8)(data)?;
+        let ip = reply.get_array::<_, 4>()?;
+        let mask = reply.get_array::<_, 4>()?;
+        let gateway = reply.get_array::<_, 4>()?;
 
-        let (_, result) = streaming::le_u32(data)?;
+        let result = reply.get_u32_le()?;
         if result != 0 {
             Err(Err::RPCErr(result as i32))
+        } else if Ipv4Addr::from(ip).is_unspecified() {
+            Ok(None)
         } else {
-            Ok(super::IPInfo {
-                ip: Ipv4Addr::new(ip[0], ip[1], ip[2], ip[3]),
-                netmask: Ipv4Addr::new(mask[0], mask[1], mask[2], mask[3]),
-                gateway: Ipv4Addr::new(gateway[0], gateway[1], gateway[2], gateway[3]),
-            })
+            Ok(Some(super::IPInfo {
+                ip: ip.into(),
+                netmask: mask.into(),
+                gateway: gateway.into(),
+            }))
+        }
+    }
+}
+
+/// Returns the DNS server configured in the given slot, or None if the slot
+/// is unset (0.0.0.0).
+pub struct GetDNSInfo {
+    pub interface: super::L3Interface,
+    pub slot: super::DNSSlot,
+}
+
+impl super::RPC for GetDNSInfo {
+    type ReturnValue = Option<Ipv4Addr>;
+    type Error = i32;
+
+    const SERVICE: ids::Service = ids::Service::TCPIP;
+    const REQUEST: u8 = ids::TCPIPRequest::GetDNSInfo as u8;
+
+    fn is_idempotent(&self) -> bool {
+        true
+    }
+
+    fn write_args(&self, w: &mut codec::ArgWriter) {
+        w.put_u32_le(self.interface as u32)
+            .put_u32_le(self.slot as u32);
+    }
+
+    fn parse(&mut self, data: &[u8]) -> Result<Self::ReturnValue, Err<Self::Error>> {
+        let (data, hdr) = codec::Header::parse(data)?;
+        hdr.expect_reply(Self::SERVICE, Self::REQUEST)?;
+        if data.is_empty() {
+            return Err(Err::EmptyReply);
+        }
+
+        let mut reply = codec::ArgReader::new(data);
+        let length = reply.get_len_prefix(4)?;
+        if length != 4 {
+            return Err(Err::UnexpectedLength {
+                declared: length,
+                expected: 4,
+            });
+        }
+        let dns = Ipv4Addr::from(reply.get_array::<_, 4>()?);
+
+        let result = reply.get_i32_le()?;
+        if result != 0 {
+            Err(Err::RPCErr(result))
+        } else if dns.is_unspecified() {
+            Ok(None)
+        } else {
+            Ok(Some(dns))
+        }
+    }
+}
+
+/// Sets the DNS server in the given slot.
+pub struct SetDNSInfo {
+    pub interface: super::L3Interface,
+    pub slot: super::DNSSlot,
+    pub server: Ipv4Addr,
+}
+
+impl super::RPC for SetDNSInfo {
+    type ReturnValue = i32;
+    type Error = ();
+
+    const SERVICE: ids::Service = ids::Service::TCPIP;
+    const REQUEST: u8 = ids::TCPIPRequest::SetDNSInfo as u8;
+
+    fn write_args(&self, w: &mut codec::ArgWriter) {
+        w.put_u32_le(self.interface as u32)
+            .put_u32_le(self.slot as u32)
+            .put_len_prefixed(&self.server.octets());
+    }
+
+    fn parse(&mut self, data: &[u8]) -> Result<Self::ReturnValue, Err<Self::Error>> {
+        let (data, hdr) = codec::Header::parse(data)?;
+        hdr.expect_reply(Self::SERVICE, Self::REQUEST)?;
+
+        let (_, ret_val) = streaming::le_i32(data)?;
+        Ok(ret_val)
+    }
+}
+
+/// Returns the MAC address of the given interface. Unlike the Wifi
+/// service's GetMacAddress, this can report the AP interface's address.
+pub struct GetMAC {
+    pub interface: super::L3Interface,
+}
+
+impl super::RPC for GetMAC {
+    type ReturnValue = super::BSSID;
+    type Error = i32;
+
+    const SERVICE: ids::Service = ids::Service::TCPIP;
+    const REQUEST: u8 = ids::TCPIPRequest::GetMAC as u8;
+
+    fn is_idempotent(&self) -> bool {
+        true
+    }
+
+    fn write_args(&self, w: &mut codec::ArgWriter) {
+        w.put_u32_le(self.interface as u32);
+    }
+
+    fn parse(&mut self, data: &[u8]) -> Result<Self::ReturnValue, Err<Self::Error>> {
+        let (data, hdr) = codec::Header::parse(data)?;
+        hdr.expect_reply(Self::SERVICE, Self::REQUEST)?;
+        if data.is_empty() {
+            return Err(Err::EmptyReply);
+        }
+
+        let mut reply = codec::ArgReader::new(data);
+        let length = reply.get_len_prefix(4)?;
+        if length != 6 {
+            return Err(Err::UnexpectedLength {
+                declared: length,
+                expected: 6,
+            });
         }
+        let mac = reply.get_array::<_, 6>()?;
+
+        let result = reply.get_i32_le()?;
+        if result != 0 {
+            Err(Err::RPCErr(result))
+        } else {
+            Ok(super::BSSID(mac))
+        }
+    }
+}
+
+/// Returns the hostname of the given interface, which is empty if it was
+/// never set.
+pub struct GetHostname {
+    pub interface: super::L3Interface,
+}
+
+impl super::RPC for GetHostname {
+    type ReturnValue = String<U32>;
+    type Error = i32;
+
+    const SERVICE: ids::Service = ids::Service::TCPIP;
+    const REQUEST: u8 = ids::TCPIPRequest::GetHostname as u8;
+
+    fn is_idempotent(&self) -> bool {
+        true
+    }
+
+    fn write_args(&self, w: &mut codec::ArgWriter) {
+        w.put_u32_le(self.interface as u32);
+    }
+
+    fn parse(&mut self, data: &[u8]) -> Result<Self::ReturnValue, Err<Self::Error>> {
+        let (data, hdr) = codec::Header::parse(data)?;
+        hdr.expect_reply(Self::SERVICE, Self::REQUEST)?;
+        if data.is_empty() {
+            return Err(Err::EmptyReply);
+        }
+
+        let mut reply = codec::ArgReader::new(data);
+        let hostname = reply.get_len_prefixed_string(4)?;
+
+        let result = reply.get_i32_le()?;
+        if result != 0 {
+            Err(Err::RPCErr(result))
+        } else {
+            Ok(hostname)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::parse_reply;
+    use crate::{Err, L3Interface, RPC};
+
+    #[test]
+    fn args_into_slice() {
+        let rpc = SetDNSInfo {
+            interface: L3Interface::AP,
+            slot: crate::DNSSlot::Backup,
+            server: Ipv4Addr::new(8, 8, 4, 4),
+        };
+        let mut buff = [0u8; 16];
+        assert_eq!(rpc.args_into(&mut buff), Ok(16));
+        assert_eq!(buff, [1, 0, 0, 0, 1, 0, 0, 0, 4, 0, 0, 0, 8, 8, 4, 4]);
+
+        let mut short = [0u8; 15];
+        assert_eq!(rpc.args_into(&mut short), Err(Err::RequestOverrun));
+    }
+
+    #[test]
+    fn get_ip_info() {
+        let mut rpc = GetIPInfo {
+            interface: L3Interface::Station,
+        };
+        let body = [
+            12, 0, 0, 0, 192, 168, 1, 20, 255, 255, 255, 0, 192, 168, 1, 1, 0, 0, 0, 0,
+        ];
+        let info = parse_reply(&mut rpc, &body).unwrap().unwrap();
+        assert_eq!(info.ip, Ipv4Addr::new(192, 168, 1, 20));
+        assert_eq!(info.netmask, Ipv4Addr::new(255, 255, 255, 0));
+        assert_eq!(info.gateway, Ipv4Addr::new(192, 168, 1, 1));
+    }
+
+    #[test]
+    fn get_ip_info_unassigned() {
+        let mut rpc = GetIPInfo {
+            interface: L3Interface::Station,
+        };
+        let mut body = [0u8; 20];
+        body[0] = 12;
+        assert!(parse_reply(&mut rpc, &body).unwrap().is_none());
+    }
+
+    #[test]
+    fn get_ip_info_error_status() {
+        let mut rpc = GetIPInfo {
+            interface: L3Interface::Station,
+        };
+        let mut body = [0u8; 20];
+        body[0] = 12;
+        body[16] = 5;
+        assert_eq!(parse_reply(&mut rpc, &body).unwrap_err(), Err::RPCErr(5));
+    }
+
+    #[test]
+    fn get_dns_info() {
+        let mut rpc = GetDNSInfo {
+            interface: L3Interface::Station,
+            slot: crate::DNSSlot::Main,
+        };
+        let body = [4, 0, 0, 0, 1, 1, 1, 1, 0, 0, 0, 0];
+        assert_eq!(
+            parse_reply(&mut rpc, &body),
+            Ok(Some(Ipv4Addr::new(1, 1, 1, 1)))
+        );
+
+        let unset = [4, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0];
+        assert_eq!(parse_reply(&mut rpc, &unset), Ok(None));
+    }
+
+    #[test]
+    fn get_dns_info_wrong_size() {
+        let mut rpc = GetDNSInfo {
+            interface: L3Interface::Station,
+            slot: crate::DNSSlot::Backup,
+        };
+        let body = [6, 0, 0, 0, 1, 1, 1, 1, 1, 1, 0, 0, 0, 0];
+        assert_eq!(
+            parse_reply(&mut rpc, &body),
+            Err(Err::UnexpectedLength {
+                declared: 6,
+                expected: 4
+            })
+        );
+    }
+
+    #[test]
+    fn dns_info_from_slots() {
+        let (a, b) = (Ipv4Addr::new(1, 1, 1, 1), Ipv4Addr::new(8, 8, 8, 8));
+        let info = crate::DNSInfo::from_slots(Some(a), Some(Ipv4Addr::new(0, 0, 0, 0))).unwrap();
+        assert_eq!((info.primary, info.secondary), (a, None));
+        let info = crate::DNSInfo::from_slots(None, Some(b)).unwrap();
+        assert_eq!((info.primary, info.secondary), (b, None));
+        let info = crate::DNSInfo::from_slots(Some(a), Some(b)).unwrap();
+        assert_eq!((info.primary, info.secondary), (a, Some(b)));
+        assert!(crate::DNSInfo::from_slots(None, None).is_none());
+    }
+
+    #[test]
+    fn network_config_from_replies() {
+        let mut rpc = GetIPInfo {
+            interface: L3Interface::Station,
+        };
+        let body = [
+            12, 0, 0, 0, 192, 168, 1, 20, 255, 255, 255, 0, 192, 168, 1, 1, 0, 0, 0, 0,
+        ];
+        let ip = parse_reply(&mut rpc, &body).unwrap().unwrap();
+        let dns = Ipv4Addr::new(192, 168, 1, 1);
+        let config = crate::NetworkConfig::from_replies(ip, Some(dns), None);
+        assert_eq!(config.ip.ip, Ipv4Addr::new(192, 168, 1, 20));
+        let dns_info = config.dns.unwrap();
+        assert_eq!((dns_info.primary, dns_info.secondary), (dns, None));
+    }
+
+    #[test]
+    fn get_ip_info_wrong_size() {
+        let mut rpc = GetIPInfo {
+            interface: L3Interface::Station,
+        };
+        let body = [8, 0, 0, 0, 192, 168, 1, 20, 255, 255, 255, 0, 0, 0, 0, 0];
+        assert_eq!(
+            parse_reply(&mut rpc, &body).unwrap_err(),
+            Err::UnexpectedLength {
+                declared: 8,
+                expected: 12
+            }
+        );
+    }
+
+    #[test]
+    fn get_ip_info_short_status() {
+        let mut rpc = GetIPInfo {
+            interface: L3Interface::Station,
+        };
+        let body = [
+            12, 0, 0, 0, 192, 168, 1, 20, 255, 255, 255, 0, 192, 168, 1, 1, 0, 0, 0,
+        ];
+        assert_eq!(
+            parse_reply(&mut rpc, &body).unwrap_err(),
+            Err::Incomplete { needed: 1 }
+        );
+    }
+
+    #[test]
+    fn get_hostname() {
+        let mut rpc = GetHostname {
+            interface: L3Interface::Station,
+        };
+        let body = b"\x04\x00\x00\x00wio1\x00\x00\x00\x00";
+        assert_eq!(parse_reply(&mut rpc, body).unwrap().as_str(), "wio1");
+
+        let empty = [0u8; 8];
+        assert_eq!(parse_reply(&mut rpc, &empty).unwrap().as_str(), "");
+
+        let mut long = [b'a'; 4 + 33 + 4];
+        long[..4].copy_from_slice(&33u32.to_le_bytes());
+        long[37..].copy_from_slice(&[0; 4]);
+        assert_eq!(parse_reply(&mut rpc, &long), Err(Err::ResponseOverrun));
+    }
+
+    #[test]
+    fn empty_replies() {
+        let interface = L3Interface::Station;
+        assert_eq!(
+            parse_reply(&mut GetIPInfo { interface }, &[]).unwrap_err(),
+            Err::EmptyReply
+        );
+        let slot = crate::DNSSlot::Main;
+        assert_eq!(
+            parse_reply(&mut GetDNSInfo { interface, slot }, &[]),
+            Err(Err::EmptyReply)
+        );
+        assert_eq!(
+            parse_reply(&mut GetHostname { interface }, &[]),
+            Err(Err::EmptyReply)
+        );
+    }
+
+    #[test]
+    fn get_mac() {
+        let mut rpc = GetMAC {
+            interface: L3Interface::AP,
+        };
+        let body = [6, 0, 0, 0, 0x02, 0x11, 0x22, 0x33, 0x44, 0x55, 0, 0, 0, 0];
+        assert_eq!(
+            parse_reply(&mut rpc, &body).unwrap().0,
+            [0x02, 0x11, 0x22, 0x33, 0x44, 0x55]
+        );
+
+        let mut failed = body;
+        failed[10] = 3;
+        assert_eq!(parse_reply(&mut rpc, &failed).unwrap_err(), Err::RPCErr(3));
+
+        let short = [4, 0, 0, 0, 0x02, 0x11, 0x22, 0x33, 0, 0, 0, 0];
+        assert_eq!(
+            parse_reply(&mut rpc, &short).unwrap_err(),
+            Err::UnexpectedLength {
+                declared: 4,
+                expected: 6
+            }
+        );
     }
 }
@@ -0,0 +1,41 @@
+use no_std_net::Ipv4Addr;
+
+/// Returns the CIDR prefix length of a netmask, such as 24 for
+/// 255.255.255.0, or None if the set bits of the mask are not contiguous.
+pub fn netmask_to_prefix(mask: Ipv4Addr) -> Option<u8> {
+    let mask = u32::from(mask);
+    let prefix = mask.leading_ones();
+    if mask.checked_shl(prefix).unwrap_or(0) == 0 {
+        Some(prefix as u8)
+    } else {
+        None
+    }
+}
+
+/// Returns the netmask for a CIDR prefix length. Lengths above 32 are
+/// treated as 32.
+pub fn prefix_to_netmask(prefix: u8) -> Ipv4Addr {
+    let prefix = prefix.min(32) as u32;
+    Ipv4Addr::from(u32::MAX.checked_shl(32 - prefix).unwrap_or(0))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn netmask_prefix() {
+        assert_eq!(netmask_to_prefix(Ipv4Addr::new(0, 0, 0, 0)), Some(0));
+        assert_eq!(netmask_to_prefix(Ipv4Addr::new(255, 255, 255, 0)), Some(24));
+        assert_eq!(
+            netmask_to_prefix(Ipv4Addr::new(255, 255, 255, 255)),
+            Some(32)
+        );
+        assert_eq!(netmask_to_prefix(Ipv4Addr::new(255, 0, 255, 0)), None);
+
+        assert_eq!(prefix_to_netmask(0), Ipv4Addr::new(0, 0, 0, 0));
+        assert_eq!(prefix_to_netmask(24), Ipv4Addr::new(255, 255, 255, 0));
+        assert_eq!(prefix_to_netmask(32), Ipv4Addr::new(255, 255, 255, 255));
+        assert_eq!(prefix_to_netmask(33), Ipv4Addr::new(255, 255, 255, 255));
+    }
+}
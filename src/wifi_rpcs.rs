@@ -1,49 +1,57 @@
 #[allow(dead_code)]
 use super::{codec, ids, Err};
-use generic_array::{ArrayLength, GenericArray};
+use generic_array::{typenum::Unsigned, ArrayLength, GenericArray};
 use heapless::{
     consts::{U18, U64},
     String,
 };
-use nom::{
-    bytes::streaming::take, lib::std::ops::RangeFrom, lib::std::ops::RangeTo, number::streaming,
-    InputIter, InputLength, Slice,
-};
+use nom::{number::streaming, InputIter};
+
+/// The size of the MAC address field in a GetMacAddress reply: 17
+/// characters of colon-separated hex followed by a NUL.
+const MAC_FIELD_LEN: usize = 18;
+
+/// The size of a single scan record on the wire: the SSID (length and
+/// value), BSSID, RSSI and then five u32 fields.
+const SCAN_RECORD_LEN: usize = 62;
+const _: () = assert!(
+    core::mem::size_of::<super::SSID>() + core::mem::size_of::<super::BSSID>() + 2 + 5 * 4
+        == SCAN_RECORD_LEN
+);
 
-/// Returns the mac address as a colon-separated hex string.
+/// Returns the mac address as a colon-separated hex string, which can be
+/// parsed into a BSSID.
+///
+/// This reports a single address, even in station+AP mode. Use the TCPIP
+/// service's GetMAC to get the address of a specific interface.
 pub struct GetMacAddress {}
 
 impl super::RPC for GetMacAddress {
     type ReturnValue = String<U18>;
     type Error = i32;
 
-    fn header(&self, seq: u32) -> codec::Header {
-        codec::Header {
-            sequence: seq,
-            msg_type: ids::MsgType::Invocation,
-            service: ids::Service::Wifi,
-            request: ids::WifiRequest::GetMacAddress.into(),
-        }
+    const SERVICE: ids::Service = ids::Service::Wifi;
+    const REQUEST: u8 = ids::WifiRequest::GetMacAddress as u8;
+
+    fn is_idempotent(&self) -> bool {
+        true
     }
 
     fn parse(&mut self, data: &[u8]) -> Result<Self::ReturnValue, Err<Self::Error>> {
         let (data, hdr) = codec::Header::parse(data)?;
-        if hdr.msg_type != ids::MsgType::Reply
-            || hdr.service != ids::Service::Wifi
-            || hdr.request != ids::WifiRequest::GetMacAddress.into()
-        {
-            return Err(Err::NotOurs);
+        hdr.expect_reply(Self::SERVICE, Self::REQUEST)?;
+        if data.is_empty() {
+            return Err(Err::EmptyReply);
         }
 
-        if data.input_len() < 18 {
-            return Err(Err::RPCErr(-1));
-        }
+        let mut reply = codec::ArgReader::new(data);
+        let field = reply.get_bytes(MAC_FIELD_LEN)?;
         let mut mac: String<U18> = String::new();
-        for b in data.slice(RangeTo { end: 17 }).iter_elements() {
-            mac.push(b as char).map_err(|_| Err::ResponseOverrun)?;
+        for b in &field[..MAC_FIELD_LEN - 1] {
+            mac.push(*b as char).map_err(|_| Err::ResponseOverrun)?;
         }
 
-        let (_, result) = streaming::le_u32(data.slice(RangeFrom { start: 18 }))?;
+        let result = reply.get_u32_le()?;
         if result != 0 {
             Err(Err::RPCErr(result as i32))
         } else {
@@ -52,6 +60,94 @@ impl super::RPC for GetMacAddress {
     }
 }
 
+/// Returns the signal strength of the connected AP in dBm. This is the only
+/// link statistic the firmware reports.
+pub struct GetRSSI {}
+
+impl super::RPC for GetRSSI {
+    type ReturnValue = i16;
+    type Error = i32;
+
+    const SERVICE: ids::Service = ids::Service::Wifi;
+    const REQUEST: u8 = ids::WifiRequest::GetRSSI as u8;
+
+    fn is_idempotent(&self) -> bool {
+        true
+    }
+
+    fn parse(&mut self, data: &[u8]) -> Result<Self::ReturnValue, Err<Self::Error>> {
+        let (data, hdr) = codec::Header::parse(data)?;
+        hdr.expect_reply(Self::SERVICE, Self::REQUEST)?;
+        if data.is_empty() {
+            return Err(Err::EmptyReply);
+        }
+
+        let mut reply = codec::ArgReader::new(data);
+        let rssi = reply.get_i32_le()?;
+        let result = reply.get_i32_le()?;
+        if result != 0 {
+            Err(Err::RPCErr(result))
+        } else {
+            Ok(rssi as i16)
+        }
+    }
+}
+
+/// Returns true if the given interface is up. This only indicates the radio
+/// is on, not that it is associated with a network.
+pub struct IsUp {
+    pub interface: super::L3Interface,
+}
+
+impl super::RPC for IsUp {
+    type ReturnValue = bool;
+    type Error = ();
+
+    const SERVICE: ids::Service = ids::Service::Wifi;
+    const REQUEST: u8 = ids::WifiRequest::IsUp as u8;
+
+    fn is_idempotent(&self) -> bool {
+        true
+    }
+
+    fn write_args(&self, w: &mut codec::ArgWriter) {
+        w.put_u32_le(self.interface as u32);
+    }
+
+    fn parse(&mut self, data: &[u8]) -> Result<Self::ReturnValue, Err<Self::Error>> {
+        let (data, hdr) = codec::Header::parse(data)?;
+        hdr.expect_reply(Self::SERVICE, Self::REQUEST)?;
+
+        let (_, up) = streaming::le_i32(data)?;
+        Ok(up == 1)
+    }
+}
+
+/// Returns true if the station is associated with an AP. This does not
+/// indicate an IP address has been assigned; use GetIPInfo for that.
+pub struct IsConnectedToAP {}
+
+impl super::RPC for IsConnectedToAP {
+    type ReturnValue = bool;
+    type Error = ();
+
+    const SERVICE: ids::Service = ids::Service::Wifi;
+    const REQUEST: u8 = ids::WifiRequest::IsConnectedToAP as u8;
+
+    fn is_idempotent(&self) -> bool {
+        true
+    }
+
+    fn parse(&mut self, data: &[u8]) -> Result<Self::ReturnValue, Err<Self::Error>> {
+        let (data, hdr) = codec::Header::parse(data)?;
+        hdr.expect_reply(Self::SERVICE, Self::REQUEST)?;
+
+        // The firmware returns 0 (RTW_SUCCESS) when connected.
+        let (_, ret_val) = streaming::le_i32(data)?;
+        Ok(ret_val == 0)
+    }
+}
+
 /// Returns true if the wifi chip is currently scanning.
 pub struct IsScanning {}
 
@@ -59,27 +155,20 @@ impl super::RPC for IsScanning {
     type ReturnValue = bool;
     type Error = ();
 
-    fn header(&self, seq: u32) -> codec::Header {
-        codec::Header {
-            sequence: seq,
-            msg_type: ids::MsgType::Invocation,
-            service: ids::Service::Wifi,
-            request: ids::WifiRequest::IsScanning.into(),
-        }
+    const SERVICE: ids::Service = ids::Service::Wifi;
+    const REQUEST: u8 = ids::WifiRequest::IsScanning as u8;
+
+    fn is_idempotent(&self) -> bool {
+        true
     }
 
     fn parse(&mut self, data: &[u8]) -> Result<Self::ReturnValue, Err<Self::Error>> {
         let (data, hdr) = codec::Header::parse(data)?;
-        if hdr.msg_type != ids::MsgType::Reply
-            || hdr.service != ids::Service::Wifi
-            || hdr.request != ids::WifiRequest::IsScanning.into()
-        {
-            return Err(Err::NotOurs);
+        hdr.expect_reply(Self::SERVICE, Self::REQUEST)?;
+        if data.is_empty() {
+            return Err(Err::EmptyReply);
         }
 
-        if data.input_len() < 1 {
-            return Err(Err::RPCErr(()));
-        }
         Ok(data.iter_elements().nth(0) != Some(0))
     }
 }
@@ -105,6 +194,51 @@ pub struct ScanResult {
     pub band: super::Band,
 }
 
+impl ScanResult {
+    /// Decodes a single 62-byte scan record. The record is laid out as the
+    /// SSID length (1 byte), SSID (33), BSSID (6), RSSI (i16), then bss_type,
+    /// security, wps, chan and band as u32s. Every byte is accounted for: the
+    /// firmware does not report when a network was last seen.
+    pub fn parse<E>(reply: &mut codec::ArgReader) -> Result<Self, Err<E>> {
+        let ssid_len = reply.get_u8()?;
+        let ssid_data = reply.get_array()?;
+        let bssid = reply.get_array()?;
+        let rssi = reply.get_i16_le()?;
+        let bss_type = reply.get_u32_le()?;
+        let security = reply.get_u32_le()?;
+        let wps = reply.get_u32_le()?;
+        let chan = reply.get_u32_le()?;
+        let band = reply.get_u32_le()?;
+
+        Ok(ScanResult {
+            ssid: super::SSID {
+                // A corrupt length would otherwise slice past the value.
+                len: ssid_len.min(32),
+                value: ssid_data,
+            },
+            bssid: super::BSSID(bssid),
+            rssi,
+            bss_type: bss_type.into(),
+            // Unknown bits are kept so newer ciphers are not mistaken for an
+            // open network.
+            security: super::Security::from_wire(security),
+            wps: wps.into(),
+            chan,
+            band: band.into(),
+        })
+    }
+
+    /// Returns true if the network can be joined without a password.
+    pub fn is_open(&self) -> bool {
+        self.security.is_open()
+    }
+
+    /// Returns true if a password is needed to join the network.
+    pub fn requires_password(&self) -> bool {
+        !self.is_open()
+    }
+}
+
 impl core::fmt::Debug for ScanResult {
     fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
         // Unused unsafe warning is erroneous: needed for safe_packed_borrows
@@ -136,6 +270,24 @@ impl core::fmt::Debug for ScanResult {
     }
 }
 
+impl core::fmt::Display for ScanResult {
+    /// Formats the result as a one-line summary like
+    /// "MyWifi  -62dBm  ch6  WPA2". Hidden networks are shown by BSSID.
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        if self.ssid.as_bytes().is_empty() {
+            write!(f, "{}", self.bssid)?;
+        } else {
+            write!(f, "{:?}", self.ssid)?;
+        }
+        write!(f, "  {}dBm  ch{}  ", self.rssi, self.chan)?;
+        if self.security.unknown_bits() != 0 {
+            write!(f, "unknown security ({:#x})", self.security.as_wire())
+        } else {
+            f.write_str(self.security.describe())
+        }
+    }
+}
+
 impl Default for ScanResult {
     fn default() -> Self {
         Self {
@@ -154,8 +306,71 @@ impl Default for ScanResult {
     }
 }
 
+/// Decodes the scan records in the body of a ScanGetAP reply (the part after
+/// the header, starting with the length prefix), without needing to know the
+/// number of records up front. Decoding stops after the first error.
+pub fn decode_scan_records(body: &[u8]) -> impl Iterator<Item = Result<ScanResult, Err<()>>> + '_ {
+    let mut err = None;
+    let records = match codec::ArgReader::new(body).get_len_prefixed() {
+        Ok(records) => records,
+        Err(e) => {
+            err = Some(e);
+            &[]
+        }
+    };
+
+    let mut records = codec::ArgReader::new(records);
+    core::iter::from_fn(move || {
+        if let Some(e) = err.take() {
+            return Some(Err(e));
+        }
+        if records.remaining().is_empty() {
+            return None;
+        }
+
+        let res = ScanResult::parse(&mut records);
+        if res.is_err() {
+            records = codec::ArgReader::new(&[]);
+        }
+        Some(res)
+    })
+}
+
+/// Parses a ScanGetAP reply which should carry count records, passing each
+/// record to sink along with its index, and returns the trailing status.
+fn parse_scan_reply<E>(
+    data: &[u8],
+    count: usize,
+    mut sink: impl FnMut(usize, ScanResult),
+) -> Result<i32, Err<E>> {
+    let (data, hdr) = codec::Header::parse(data)?;
+    hdr.expect_reply(ids::Service::Wifi, ids::WifiRequest::ScanGetAP as u8)?;
+    if data.is_empty() {
+        return Err(Err::EmptyReply);
+    }
+
+    let mut reply = codec::ArgReader::new(data);
+    let l = reply.get_len_prefix(4)?; // Binary len - returning 62 bytes per result
+    if l != SCAN_RECORD_LEN * count {
+        return Err(Err::UnexpectedLength {
+            declared: l,
+            expected: SCAN_RECORD_LEN * count,
+        });
+    }
+
+    for i in 0..count {
+        sink(i, ScanResult::parse(&mut reply)?);
+    }
+    reply.get_i32_le()
+}
+
 /// Returns N number of scan results. This RPC must only be called after starting a
 /// scan, and after IsScanning returns false.
+///
+/// N should be no larger than the count returned by ScanGetNumAPs, as the
+/// firmware has no valid records beyond the APs it found. Each result
+/// occupies 62 bytes on the wire, so keep N small on memory-constrained
+/// devices.
 pub struct ScanGetAP<N: ArrayLength<ScanResult>> {
     m: core::marker::PhantomData<N>,
 }
@@ -168,68 +383,89 @@ impl<N: ArrayLength<ScanResult>> ScanGetAP<N> {
     }
 }
 
+impl<N: ArrayLength<ScanResult>> Default for ScanGetAP<N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl<N: ArrayLength<ScanResult>> super::RPC for ScanGetAP<N> {
     type ReturnValue = (GenericArray<ScanResult, N>, i32);
     type Error = usize;
 
-    fn header(&self, seq: u32) -> codec::Header {
-        codec::Header {
-            sequence: seq,
-            msg_type: ids::MsgType::Invocation,
-            service: ids::Service::Wifi,
-            request: ids::WifiRequest::ScanGetAP.into(),
-        }
+    const SERVICE: ids::Service = ids::Service::Wifi;
+    const REQUEST: u8 = ids::WifiRequest::ScanGetAP as u8;
+
+    fn is_idempotent(&self) -> bool {
+        true
     }
 
-    fn args(&self, buff: &mut heapless::Vec<u8, heapless::consts::U64>) {
-        let num = N::to_u16().to_le_bytes();
-        buff.extend_from_slice(&num).ok();
+    fn write_args(&self, w: &mut codec::ArgWriter) {
+        w.put_u16_le(N::to_u16());
     }
 
     fn parse(&mut self, data: &[u8]) -> Result<Self::ReturnValue, Err<Self::Error>> {
-        let (data, hdr) = codec::Header::parse(data)?;
-        if hdr.msg_type != ids::MsgType::Reply
-            || hdr.service != ids::Service::Wifi
-            || hdr.request != ids::WifiRequest::ScanGetAP.into()
-        {
-            return Err(Err::NotOurs);
-        }
+        let mut res = GenericArray::<ScanResult, N>::default();
+        let ret_val = parse_scan_reply(data, N::to_usize(), |i, r| res[i] = r)?;
+        Ok((res, ret_val))
+    }
+}
 
-        let (mut data, l) = streaming::le_u32(data)?; // Binary len - returning 62 bytes per result
-        if l as usize != (62 * N::to_usize()) {
-            return Err(Err::ResponseOverrun);
-        }
+/// Returns N number of scan results, like ScanGetAP, but sized with a const
+/// generic rather than a typenum. This RPC must only be called after starting
+/// a scan, and after IsScanning returns false.
+#[derive(Default)]
+pub struct ScanGetAPN<const N: usize> {}
 
-        use core::convert::TryInto;
-        let mut res = GenericArray::<ScanResult, N>::default();
-        for i in 0..N::to_usize() {
-            let (d, ssid_len) = streaming::le_u8(data)?;
-            let (d, ssid_data) = take(33usize)(d)?;
-            let (d, bssid) = take(6usize)(d)?;
-            let (d, rssi) = streaming::le_i16(d)?;
-            let (d, bss_type) = streaming::le_u32(d)?;
-            let (d, security) = streaming::le_u32(d)?;
-            let (d, wps) = streaming::le_u32(d)?;
-            let (d, chan) = streaming::le_u32(d)?;
-            let (d, band) = streaming::le_u32(d)?;
-
-            res[i] = ScanResult {
-                ssid: super::SSID {
-                    len: ssid_len,
-                    value: ssid_data.try_into().unwrap(),
-                },
-                bssid: super::BSSID(bssid.try_into().unwrap()),
-                rssi,
-                bss_type: bss_type.into(),
-                security: super::Security::from_bits_truncate(security),
-                wps: wps.into(),
-                chan,
-                band: band.into(),
-            };
-            data = d;
-        }
+impl<const N: usize> super::RPC for ScanGetAPN<N> {
+    type ReturnValue = ([ScanResult; N], i32);
+    type Error = usize;
 
-        let (_, ret_val) = streaming::le_i32(data)?;
+    const SERVICE: ids::Service = ids::Service::Wifi;
+    const REQUEST: u8 = ids::WifiRequest::ScanGetAP as u8;
+
+    fn is_idempotent(&self) -> bool {
+        true
+    }
+
+    fn write_args(&self, w: &mut codec::ArgWriter) {
+        w.put_u16_le(N as u16);
+    }
+
+    fn parse(&mut self, data: &[u8]) -> Result<Self::ReturnValue, Err<Self::Error>> {
+        let mut res = [ScanResult::default(); N];
+        let ret_val = parse_scan_reply(data, N, |i, r| res[i] = r)?;
+        Ok((res, ret_val))
+    }
+}
+
+/// Returns the given number of scan results, like ScanGetAP, but with the count
+/// chosen at runtime and the results stored on the heap. This RPC must only be
+/// called after starting a scan, and after IsScanning returns false.
+#[cfg(feature = "alloc")]
+pub struct ScanGetAPVec {
+    pub count: u16,
+}
+
+#[cfg(feature = "alloc")]
+impl super::RPC for ScanGetAPVec {
+    type ReturnValue = (alloc::vec::Vec<ScanResult>, i32);
+    type Error = usize;
+
+    const SERVICE: ids::Service = ids::Service::Wifi;
+    const REQUEST: u8 = ids::WifiRequest::ScanGetAP as u8;
+
+    fn is_idempotent(&self) -> bool {
+        true
+    }
+
+    fn write_args(&self, w: &mut codec::ArgWriter) {
+        w.put_u16_le(self.count);
+    }
+
+    fn parse(&mut self, data: &[u8]) -> Result<Self::ReturnValue, Err<Self::Error>> {
+        let mut res = alloc::vec::Vec::with_capacity(self.count as usize);
+        let ret_val = parse_scan_reply(data, self.count as usize, |_, r| res.push(r))?;
         Ok((res, ret_val))
     }
 }
@@ -241,56 +477,46 @@ impl super::RPC for ScanGetNumAPs {
     type ReturnValue = u16;
     type Error = ();
 
-    fn header(&self, seq: u32) -> codec::Header {
-        codec::Header {
-            sequence: seq,
-            msg_type: ids::MsgType::Invocation,
-            service: ids::Service::Wifi,
-            request: ids::WifiRequest::ScanGetNumAPs.into(),
-        }
+    const SERVICE: ids::Service = ids::Service::Wifi;
+    const REQUEST: u8 = ids::WifiRequest::ScanGetNumAPs as u8;
+
+    fn is_idempotent(&self) -> bool {
+        true
     }
 
     fn parse(&mut self, data: &[u8]) -> Result<Self::ReturnValue, Err<Self::Error>> {
         let (data, hdr) = codec::Header::parse(data)?;
-        if hdr.msg_type != ids::MsgType::Reply
-            || hdr.service != ids::Service::Wifi
-            || hdr.request != ids::WifiRequest::ScanGetNumAPs.into()
-        {
-            return Err(Err::NotOurs);
+        hdr.expect_reply(Self::SERVICE, Self::REQUEST)?;
+        if data.is_empty() {
+            return Err(Err::EmptyReply);
         }
 
-        if data.input_len() < 2 {
-            return Err(Err::RPCErr(()));
-        }
         let (_, num) = streaming::le_u16(data)?;
         Ok(num)
     }
 }
 
 /// Initiates a network scan. A return value of 0 indicates success afaict.
+///
+/// The firmware provides no way to abort a scan once started, so the radio
+/// is unavailable until IsScanning returns false.
 pub struct ScanStart {}
 
 impl super::RPC for ScanStart {
     type ReturnValue = i32;
     type Error = ();
 
-    fn header(&self, seq: u32) -> codec::Header {
-        codec::Header {
-            sequence: seq,
-            msg_type: ids::MsgType::Invocation,
-            service: ids::Service::Wifi,
-            request: ids::WifiRequest::ScanStart.into(),
-        }
+    const SERVICE: ids::Service = ids::Service::Wifi;
+    const REQUEST: u8 = ids::WifiRequest::ScanStart as u8;
+
+    fn timeout_hint(&self) -> Option<core::time::Duration> {
+        // The radio may be busy for a while before accepting the scan.
+        Some(core::time::Duration::from_secs(5))
     }
 
     fn parse(&mut self, data: &[u8]) -> Result<Self::ReturnValue, Err<Self::Error>> {
         let (data, hdr) = codec::Header::parse(data)?;
-        if hdr.msg_type != ids::MsgType::Reply
-            || hdr.service != ids::Service::Wifi
-            || hdr.request != ids::WifiRequest::ScanStart.into()
-        {
-            return Err(Err::NotOurs);
-        }
+        hdr.expect_reply(Self::SERVICE, Self::REQUEST)?;
 
         let (_, num) = streaming::le_i32(data)?;
         Ok(num)
@@ -306,28 +532,16 @@ impl super::RPC for WifiOn {
     type ReturnValue = i32;
     type Error = ();
 
-    fn args(&self, buff: &mut heapless::Vec<u8, heapless::consts::U64>) {
-        let mode = self.mode as u32;
-        buff.extend_from_slice(&mode.to_le_bytes()).ok();
-    }
+    const SERVICE: ids::Service = ids::Service::Wifi;
+    const REQUEST: u8 = ids::WifiRequest::TurnOn as u8;
 
-    fn header(&self, seq: u32) -> codec::Header {
-        codec::Header {
-            sequence: seq,
-            msg_type: ids::MsgType::Invocation,
-            service: ids::Service::Wifi,
-            request: ids::WifiRequest::TurnOn.into(),
-        }
+    fn write_args(&self, w: &mut codec::ArgWriter) {
+        w.put_u32_le(self.mode as u32);
     }
 
     fn parse(&mut self, data: &[u8]) -> Result<Self::ReturnValue, Err<Self::Error>> {
         let (data, hdr) = codec::Header::parse(data)?;
-        if hdr.msg_type != ids::MsgType::Reply
-            || hdr.service != ids::Service::Wifi
-            || hdr.request != ids::WifiRequest::TurnOn.into()
-        {
-            return Err(Err::NotOurs);
-        }
+        hdr.expect_reply(Self::SERVICE, Self::REQUEST)?;
 
         let (_, num) = streaming::le_i32(data)?;
         Ok(num)
@@ -341,30 +555,56 @@ impl super::RPC for WifiOff {
     type ReturnValue = i32;
     type Error = ();
 
-    fn header(&self, seq: u32) -> codec::Header {
-        codec::Header {
-            sequence: seq,
-            msg_type: ids::MsgType::Invocation,
-            service: ids::Service::Wifi,
-            request: ids::WifiRequest::TurnOff.into(),
-        }
-    }
+    const SERVICE: ids::Service = ids::Service::Wifi;
+    const REQUEST: u8 = ids::WifiRequest::TurnOff as u8;
 
     fn parse(&mut self, data: &[u8]) -> Result<Self::ReturnValue, Err<Self::Error>> {
         let (data, hdr) = codec::Header::parse(data)?;
-        if hdr.msg_type != ids::MsgType::Reply
-            || hdr.service != ids::Service::Wifi
-            || hdr.request != ids::WifiRequest::TurnOff.into()
-        {
-            return Err(Err::NotOurs);
-        }
+        hdr.expect_reply(Self::SERVICE, Self::REQUEST)?;
 
         let (_, num) = streaming::le_i32(data)?;
         Ok(num)
     }
 }
 
+/// Disconnects from the current network without waiting for a reply.
+///
+/// The request is sent as a oneway message, so the firmware does not
+/// respond: the caller forgoes any confirmation that the disconnect
+/// happened. This is intended for shutdown paths where blocking on the chip
+/// is not acceptable. parse does not read anything and always succeeds.
+pub struct DisconnectOneway {}
+
+impl super::RPC for DisconnectOneway {
+    type ReturnValue = ();
+    type Error = ();
+
+    const SERVICE: ids::Service = ids::Service::Wifi;
+    const REQUEST: u8 = ids::WifiRequest::Disconnect as u8;
+
+    fn header(&self, seq: u32) -> codec::Header {
+        codec::Header {
+            sequence: seq,
+            msg_type: ids::MsgType::Oneway,
+            service: Self::SERVICE,
+            request: Self::REQUEST,
+        }
+    }
+
+    fn parse(&mut self, _data: &[u8]) -> Result<Self::ReturnValue, Err<Self::Error>> {
+        Ok(())
+    }
+}
+
+/// The largest arguments WifiConnect can encode: the length-prefixed SSID,
+/// the nullable password, and the security, key_id and semaphore words.
+const WIFI_CONNECT_MAX_ARGS: usize = (4 + U64::USIZE) + (1 + 4 + U64::USIZE) + 3 * 4;
+const _: () = assert!(WIFI_CONNECT_MAX_ARGS <= codec::MAX_ARGS_LEN);
+
 /// Connects to the network with the provided properties.
+///
+/// The reply carries a single status value, which is 0 once the join has
+/// succeeded. There is no separate firmware status word.
 pub struct WifiConnect {
     pub ssid: String<U64>,
     pub password: String<U64>,
@@ -377,46 +617,504 @@ impl super::RPC for WifiConnect {
     type ReturnValue = i32;
     type Error = ();
 
-    fn args(&self, buff: &mut heapless::Vec<u8, U64>) {
-        buff.extend_from_slice(&(self.ssid.len() as u32).to_le_bytes())
-            .ok();
-        buff.extend_from_slice(self.ssid.as_ref()).ok();
-
-        // Write the nullable flag (0 = NotNull, 1 = Null)
-        buff.push(if self.password.len() > 0 { 0u8 } else { 1u8 })
-            .ok();
-        if self.password.len() > 0 {
-            buff.extend_from_slice(&(self.password.len() as u32).to_le_bytes())
-                .ok();
-            buff.extend_from_slice(self.password.as_ref()).ok();
-        }
+    const SERVICE: ids::Service = ids::Service::Wifi;
+    const REQUEST: u8 = ids::WifiRequest::Connect as u8;
 
-        buff.extend_from_slice(&(self.security.bits()).to_le_bytes())
-            .ok();
-        buff.extend_from_slice(&(0u32.wrapping_sub(1)).to_le_bytes())
-            .ok(); // key_id - always -1?
-        buff.extend_from_slice(&(self.semaphore).to_le_bytes()).ok();
+    fn timeout_hint(&self) -> Option<core::time::Duration> {
+        // The reply is only sent once the join has finished or timed out.
+        Some(core::time::Duration::from_secs(20))
     }
 
-    fn header(&self, seq: u32) -> codec::Header {
-        codec::Header {
-            sequence: seq,
-            msg_type: ids::MsgType::Invocation,
-            service: ids::Service::Wifi,
-            request: ids::WifiRequest::Connect.into(),
-        }
+    fn write_args(&self, w: &mut codec::ArgWriter) {
+        w.put_len_prefixed(self.ssid.as_ref())
+            .put_nullable_str(if !self.password.is_empty() {
+                Some(self.password.as_str())
+            } else {
+                None
+            })
+            .put_u32_le(self.security.as_wire())
+            .put_i32_le(-1) // key_id - always -1?
+            .put_u32_le(self.semaphore);
     }
 
     fn parse(&mut self, data: &[u8]) -> Result<Self::ReturnValue, Err<Self::Error>> {
         let (data, hdr) = codec::Header::parse(data)?;
-        if hdr.msg_type != ids::MsgType::Reply
-            || hdr.service != ids::Service::Wifi
-            || hdr.request != ids::WifiRequest::Connect.into()
-        {
-            return Err(Err::NotOurs);
-        }
+        hdr.expect_reply(Self::SERVICE, Self::REQUEST)?;
+
+        let (_, num) = streaming::le_i32(data)?;
+        Ok(num)
+    }
+}
+
+/// The largest arguments WifiConnectBSSID can encode: the length-prefixed
+/// BSSID, the null SSID, the nullable password, and the security, key_id and
+/// semaphore words.
+const WIFI_CONNECT_BSSID_MAX_ARGS: usize = (4 + 6) + 1 + (1 + 4 + U64::USIZE) + 3 * 4;
+const _: () = assert!(WIFI_CONNECT_BSSID_MAX_ARGS <= codec::MAX_ARGS_LEN);
+
+/// Connects to the network with the given BSSID, such as a hidden network
+/// which does not broadcast its SSID. The SSID is sent as null.
+///
+/// The reply carries a single status value, which is 0 once the join has
+/// succeeded.
+pub struct WifiConnectBSSID {
+    pub bssid: super::BSSID,
+    pub password: String<U64>,
+    pub security: super::Security,
+    pub semaphore: u32,
+}
+
+impl super::RPC for WifiConnectBSSID {
+    type ReturnValue = i32;
+    type Error = ();
+
+    const SERVICE: ids::Service = ids::Service::Wifi;
+    const REQUEST: u8 = ids::WifiRequest::ConnectBSSID as u8;
+
+    fn timeout_hint(&self) -> Option<core::time::Duration> {
+        // The reply is only sent once the join has finished or timed out.
+        Some(core::time::Duration::from_secs(20))
+    }
+
+    fn write_args(&self, w: &mut codec::ArgWriter) {
+        let bssid = self.bssid.0;
+        w.put_len_prefixed(&bssid)
+            .put_nullable_str(None)
+            .put_nullable_str(if !self.password.is_empty() {
+                Some(self.password.as_str())
+            } else {
+                None
+            })
+            .put_u32_le(self.security.as_wire())
+            .put_i32_le(-1) // key_id - always -1?
+            .put_u32_le(self.semaphore);
+    }
+
+    fn parse(&mut self, data: &[u8]) -> Result<Self::ReturnValue, Err<Self::Error>> {
+        let (data, hdr) = codec::Header::parse(data)?;
+        hdr.expect_reply(Self::SERVICE, Self::REQUEST)?;
 
         let (_, num) = streaming::le_i32(data)?;
         Ok(num)
     }
 }
+
+/// A request to join a network found by scanning.
+pub enum WifiJoin {
+    /// The network broadcasts its SSID, so is joined by name.
+    Ssid(WifiConnect),
+    /// The network is hidden (or its SSID is not valid UTF-8), so is joined
+    /// by BSSID.
+    Bssid(WifiConnectBSSID),
+}
+
+impl WifiJoin {
+    /// Returns a request to join the network described by a scan result,
+    /// reusing its SSID or BSSID and its security flags.
+    pub fn from_scan_result(result: &ScanResult, password: String<U64>) -> Self {
+        match core::str::from_utf8(result.ssid.as_bytes()) {
+            Ok(name) if !name.is_empty() => {
+                let mut ssid = String::new();
+                // An SSID is at most 32 bytes, so always fits.
+                let _ = ssid.push_str(name);
+                WifiJoin::Ssid(WifiConnect {
+                    ssid,
+                    password,
+                    security: result.security,
+                    semaphore: 0,
+                })
+            }
+            _ => WifiJoin::Bssid(WifiConnectBSSID {
+                bssid: result.bssid,
+                password,
+                security: result.security,
+                semaphore: 0,
+            }),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::parse_reply;
+    use crate::RPC;
+    use heapless::consts::U1;
+
+    /// Encodes a scan record as the firmware lays it out on the wire.
+    fn scan_record(ssid: &[u8], rssi: i16, security: u32) -> [u8; SCAN_RECORD_LEN] {
+        let mut record = [0u8; SCAN_RECORD_LEN];
+        let mut w = codec::ArgWriter::new(&mut record);
+        w.put_u8(ssid.len() as u8)
+            .put_bytes(ssid)
+            .put_bytes(&[0; 33][ssid.len()..])
+            .put_bytes(&[0x02, 0x11, 0x22, 0x33, 0x44, 0x55])
+            .put_u16_le(rssi as u16)
+            .put_u32_le(0) // bss_type
+            .put_u32_le(security)
+            .put_u32_le(0) // wps
+            .put_u32_le(6) // chan
+            .put_u32_le(1); // band
+        assert_eq!(w.finish::<()>(), Ok(SCAN_RECORD_LEN));
+        record
+    }
+
+    /// Encodes the body of a ScanGetAP reply carrying the given records.
+    fn scan_reply(records: &[[u8; SCAN_RECORD_LEN]]) -> heapless::Vec<u8, heapless::consts::U256> {
+        let mut body = heapless::Vec::new();
+        body.extend_from_slice(&((records.len() * SCAN_RECORD_LEN) as u32).to_le_bytes())
+            .unwrap();
+        for r in records {
+            body.extend_from_slice(r).unwrap();
+        }
+        body.extend_from_slice(&[0, 0, 0, 0]).unwrap();
+        body
+    }
+
+    #[test]
+    fn wifi_connect_long_credentials() {
+        let mut ssid = String::new();
+        ssid.push_str("0123456789abcdef0123456789abcdef").unwrap();
+        let mut password = String::new();
+        password
+            .push_str("0123456789abcdef0123456789abcdef0123456789abcdef0123456789abcde")
+            .unwrap();
+        let connect = WifiConnect {
+            ssid,
+            password,
+            security: crate::Security::WPA2_AES_PSK,
+            semaphore: 0,
+        };
+
+        let mut buff = heapless::Vec::new();
+        connect.args(&mut buff).unwrap();
+        assert_eq!(buff.len(), 4 + 32 + 1 + 4 + 63 + 12);
+        // The trailing key_id and semaphore must not have been dropped.
+        assert_eq!(
+            &buff[buff.len() - 8..],
+            &[0xff, 0xff, 0xff, 0xff, 0, 0, 0, 0]
+        );
+    }
+
+    #[test]
+    fn wifi_connect_args_round_trip() {
+        let mut ssid = String::new();
+        ssid.push_str("home").unwrap();
+        let mut password = String::new();
+        password.push_str("hunter22").unwrap();
+        let mut connect = WifiConnect {
+            ssid,
+            password,
+            security: crate::Security::WPA2_AES_PSK,
+            semaphore: 7,
+        };
+
+        let mut buff = heapless::Vec::new();
+        connect.args(&mut buff).unwrap();
+        let mut r = codec::ArgReader::new(&buff);
+        assert_eq!(r.get_len_prefixed_str::<()>(), Ok("home"));
+        assert_eq!(r.get_nullable_str::<()>(), Ok(Some("hunter22")));
+        assert_eq!(
+            r.get_u32_le::<()>(),
+            Ok(crate::Security::WPA2_AES_PSK.as_wire())
+        );
+        assert_eq!(r.get_i32_le::<()>(), Ok(-1));
+        assert_eq!(r.get_u32_le::<()>(), Ok(7));
+        assert!(r.remaining().is_empty());
+
+        // An empty password is sent as a null string.
+        connect.password = String::new();
+        let mut buff = heapless::Vec::new();
+        connect.args(&mut buff).unwrap();
+        let mut r = codec::ArgReader::new(&buff[8..]);
+        assert_eq!(r.get_nullable_str::<()>(), Ok(None));
+    }
+
+    #[test]
+    fn encoded_len_matches_args() {
+        let mut ssid = String::new();
+        ssid.push_str("0123456789abcdef").unwrap();
+        let mut password = String::new();
+        password
+            .push_str("0123456789abcdef0123456789abcdef!")
+            .unwrap();
+        let connect = WifiConnect {
+            ssid,
+            password,
+            security: crate::Security::WPA2_AES_PSK,
+            semaphore: 0,
+        };
+        let mut buff = heapless::Vec::new();
+        connect.args(&mut buff).unwrap();
+        assert_eq!(buff.len(), 4 + 16 + 1 + 4 + 33 + 12);
+        assert_eq!(connect.encoded_len(), 8 + buff.len());
+
+        let scan = ScanStart {};
+        assert_eq!(scan.encoded_len(), 8);
+    }
+
+    #[test]
+    fn scan_get_num_aps() {
+        assert_eq!(parse_reply(&mut ScanGetNumAPs {}, &[7, 1]), Ok(263));
+    }
+
+    #[test]
+    fn reply_for_other_rpc() {
+        let header = codec::Header {
+            sequence: 1,
+            msg_type: ids::MsgType::Reply,
+            service: ids::Service::Wifi,
+            request: ids::WifiRequest::IsScanning as u8,
+        };
+        let mut frame = heapless::Vec::<u8, heapless::consts::U32>::new();
+        codec::build_reply(&header, &[0, 0], &mut frame).unwrap();
+        assert_eq!(ScanGetNumAPs {}.parse(&frame[4..]), Err(Err::NotOurs));
+    }
+
+    #[test]
+    fn reply_with_unknown_msg_type() {
+        let request = ids::WifiRequest::ScanGetNumAPs as u8;
+        let reply = [7, request, ids::Service::Wifi as u8, 1, 1, 0, 0, 0, 3, 0];
+        let (_, hdr) = codec::Header::parse::<_, ()>(&reply[..]).unwrap();
+        assert_eq!(hdr.msg_type, ids::MsgType::Unknown);
+        assert_eq!(ScanGetNumAPs {}.parse(&reply), Err(Err::NotOurs));
+
+        // Likewise for a service this crate does not know of.
+        let reply = [2, request, 0x63, 1, 1, 0, 0, 0, 3, 0];
+        assert_eq!(ScanGetNumAPs {}.parse(&reply), Err(Err::NotOurs));
+    }
+
+    #[test]
+    fn rssi_is_signed() {
+        let record = scan_record(b"home", -92, 0);
+        assert_eq!(&record[40..42], &(-92i16).to_le_bytes());
+        let result = ScanResult::parse::<()>(&mut codec::ArgReader::new(&record)).unwrap();
+        assert_eq!(result.rssi, -92);
+
+        let (results, status) =
+            parse_reply(&mut ScanGetAP::<U1>::new(), &scan_reply(&[record])).unwrap();
+        assert_eq!((results[0].rssi, status), (-92, 0));
+
+        let mut body = [0u8; 8];
+        body[..4].copy_from_slice(&(-92i32).to_le_bytes());
+        assert_eq!(parse_reply(&mut GetRSSI {}, &body), Ok(-92));
+    }
+
+    #[test]
+    fn scan_get_ap_wrong_count() {
+        let record = scan_record(b"home", -40, 0);
+        assert_eq!(
+            parse_reply(&mut ScanGetAP::<U1>::new(), &scan_reply(&[record, record])).unwrap_err(),
+            Err::UnexpectedLength {
+                declared: 124,
+                expected: 62
+            }
+        );
+    }
+
+    #[test]
+    fn truncated_replies() {
+        assert_eq!(
+            parse_reply(&mut ScanGetNumAPs {}, &[7]),
+            Err(Err::Incomplete { needed: 1 })
+        );
+        assert_eq!(
+            parse_reply(&mut GetMacAddress {}, b"02:11:22:33:44:55\0\0\0\0"),
+            Err(Err::Incomplete { needed: 1 })
+        );
+    }
+
+    #[test]
+    fn scan_record_oversized_ssid_len() {
+        use core::convert::TryFrom;
+        use core::fmt::Write;
+
+        let mut record = scan_record(&[b'a'; 32], -40, 0);
+        record[0] = 0xff;
+        record[33] = b'b'; // The 33rd byte is never part of the SSID.
+        let result = ScanResult::parse::<()>(&mut codec::ArgReader::new(&record)).unwrap();
+        assert_eq!(result.ssid.as_bytes(), &[b'a'; 32][..]);
+
+        let mut out: String<heapless::consts::U256> = String::new();
+        write!(out, "{:?}", result).unwrap();
+        assert!(out.contains("aaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaa,"));
+
+        let ssid = String::<heapless::consts::U32>::try_from(result.ssid).unwrap();
+        assert_eq!(ssid.len(), 32);
+    }
+
+    #[test]
+    fn scan_result_unknown_security() {
+        use core::fmt::Write;
+
+        let record = scan_record(b"home", -40, 0x2000_0000);
+        let result = ScanResult::parse::<()>(&mut codec::ArgReader::new(&record)).unwrap();
+        assert_eq!(result.security.as_wire(), 0x2000_0000);
+        assert_eq!(result.security.unknown_bits(), 0x2000_0000);
+        assert!(!result.is_open());
+
+        let mut out: String<heapless::consts::U64> = String::new();
+        write!(out, "{}", result).unwrap();
+        assert_eq!(
+            out.as_str(),
+            "home  -40dBm  ch6  unknown security (0x20000000)"
+        );
+    }
+
+    #[test]
+    fn scan_get_apn() {
+        let records = [scan_record(b"home", -40, 0), scan_record(b"work", -70, 0)];
+        let (results, status) =
+            parse_reply(&mut ScanGetAPN::<2> {}, &scan_reply(&records)).unwrap();
+        assert_eq!(status, 0);
+        assert_eq!(results[0].ssid.as_bytes(), b"home");
+        assert_eq!(
+            (results[1].ssid.as_bytes(), results[1].rssi),
+            (&b"work"[..], -70)
+        );
+
+        assert_eq!(
+            parse_reply(&mut ScanGetAPN::<1> {}, &scan_reply(&records)).unwrap_err(),
+            Err::UnexpectedLength {
+                declared: 124,
+                expected: 62
+            }
+        );
+    }
+
+    #[test]
+    fn decode_two_scan_records() {
+        let records = [scan_record(b"home", -40, 0), scan_record(b"work", -70, 0)];
+        let body = scan_reply(&records);
+        let mut decoded = decode_scan_records(&body);
+        assert_eq!(decoded.next().unwrap().unwrap().ssid.as_bytes(), b"home");
+        assert_eq!(decoded.next().unwrap().unwrap().ssid.as_bytes(), b"work");
+        assert!(decoded.next().is_none());
+    }
+
+    #[test]
+    fn decode_scan_records_partial() {
+        let record = scan_record(b"home", -40, 0);
+        let mut body = heapless::Vec::<u8, heapless::consts::U128>::new();
+        body.extend_from_slice(&100u32.to_le_bytes()).unwrap();
+        body.extend_from_slice(&record).unwrap();
+        body.extend_from_slice(&record[..38]).unwrap();
+
+        let mut decoded = decode_scan_records(&body);
+        assert_eq!(decoded.next().unwrap().unwrap().ssid.as_bytes(), b"home");
+        assert!(matches!(decoded.next(), Some(Err(Err::Incomplete { .. }))));
+        assert!(decoded.next().is_none());
+    }
+
+    #[test]
+    fn empty_replies() {
+        assert_eq!(
+            parse_reply(&mut GetMacAddress {}, &[]),
+            Err(Err::EmptyReply)
+        );
+        assert_eq!(parse_reply(&mut GetRSSI {}, &[]), Err(Err::EmptyReply));
+        assert_eq!(parse_reply(&mut IsScanning {}, &[]), Err(Err::EmptyReply));
+        assert_eq!(
+            parse_reply(&mut ScanGetNumAPs {}, &[]),
+            Err(Err::EmptyReply)
+        );
+        assert_eq!(
+            parse_reply(&mut ScanGetAP::<U1>::new(), &[]).unwrap_err(),
+            Err::EmptyReply
+        );
+        assert_eq!(
+            parse_reply(&mut ScanGetAPN::<1> {}, &[]).unwrap_err(),
+            Err::EmptyReply
+        );
+        #[cfg(feature = "alloc")]
+        assert_eq!(
+            parse_reply(&mut ScanGetAPVec { count: 1 }, &[]).unwrap_err(),
+            Err::EmptyReply
+        );
+    }
+
+    #[test]
+    fn join_named_network() {
+        let record = scan_record(b"home", -40, crate::Security::WPA2_AES_PSK.as_wire());
+        let result = ScanResult::parse::<()>(&mut codec::ArgReader::new(&record)).unwrap();
+        let mut password = String::new();
+        password.push_str("hunter22").unwrap();
+
+        match WifiJoin::from_scan_result(&result, password) {
+            WifiJoin::Ssid(connect) => {
+                assert_eq!(connect.ssid.as_str(), "home");
+                assert_eq!(connect.password.as_str(), "hunter22");
+                assert_eq!(connect.security, crate::Security::WPA2_AES_PSK);
+            }
+            WifiJoin::Bssid(_) => panic!("named network joined by BSSID"),
+        }
+    }
+
+    #[test]
+    fn join_hidden_network() {
+        let record = scan_record(b"", -40, crate::Security::WPA2_AES_PSK.as_wire());
+        let result = ScanResult::parse::<()>(&mut codec::ArgReader::new(&record)).unwrap();
+        let mut password = String::new();
+        password.push_str("hunter22").unwrap();
+
+        let connect = match WifiJoin::from_scan_result(&result, password) {
+            WifiJoin::Bssid(connect) => connect,
+            WifiJoin::Ssid(_) => panic!("hidden network joined by SSID"),
+        };
+        assert_eq!(connect.bssid.0, [0x02, 0x11, 0x22, 0x33, 0x44, 0x55]);
+        assert_eq!(connect.security, crate::Security::WPA2_AES_PSK);
+
+        let mut buff = heapless::Vec::new();
+        connect.args(&mut buff).unwrap();
+        let mut r = codec::ArgReader::new(&buff);
+        assert_eq!(
+            r.get_len_prefixed::<()>(),
+            Ok(&[0x02, 0x11, 0x22, 0x33, 0x44, 0x55][..])
+        );
+        assert_eq!(r.get_nullable_str::<()>(), Ok(None));
+        assert_eq!(r.get_nullable_str::<()>(), Ok(Some("hunter22")));
+        assert_eq!(r.remaining().len(), 12);
+    }
+
+    #[test]
+    fn is_up_and_is_connected_conventions() {
+        let mut is_up = IsUp {
+            interface: crate::L3Interface::Station,
+        };
+        // IsUp replies 1 when the interface is up...
+        assert_eq!(parse_reply(&mut is_up, &[1, 0, 0, 0]), Ok(true));
+        assert_eq!(parse_reply(&mut is_up, &[0, 0, 0, 0]), Ok(false));
+        // ...while IsConnectedToAP replies 0 (RTW_SUCCESS) when connected.
+        assert_eq!(
+            parse_reply(&mut IsConnectedToAP {}, &[0, 0, 0, 0]),
+            Ok(true)
+        );
+        assert_eq!(
+            parse_reply(&mut IsConnectedToAP {}, &[1, 0, 0, 0]),
+            Ok(false)
+        );
+        assert_eq!(
+            parse_reply(&mut IsConnectedToAP {}, &[0xff, 0xff, 0xff, 0xff]),
+            Ok(false)
+        );
+    }
+
+    #[cfg(feature = "alloc")]
+    #[test]
+    fn scan_get_ap_vec() {
+        let records = [scan_record(b"home", -40, 0), scan_record(b"work", -70, 0)];
+        let (results, status) =
+            parse_reply(&mut ScanGetAPVec { count: 2 }, &scan_reply(&records)).unwrap();
+        assert_eq!((results.len(), status), (2, 0));
+        assert_eq!(results[0].ssid.as_bytes(), b"home");
+        assert_eq!(results[1].ssid.as_bytes(), b"work");
+
+        assert_eq!(
+            parse_reply(&mut ScanGetAPVec { count: 3 }, &scan_reply(&records)).unwrap_err(),
+            Err::UnexpectedLength {
+                declared: 124,
+                expected: 186
+            }
+        );
+    }
+}